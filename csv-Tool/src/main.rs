@@ -1,11 +1,35 @@
-use clap::{Parser, Subcommand};
-use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use clap::{builder::RangedU64ValueParser, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use csv::{ReaderBuilder, StringRecord, Terminator, Trim, Writer, WriterBuilder};
+use csv_ops::{
+    Accumulator, Aggregate, Filter, Template, check_distinct, diff, compute_column_stats, compute_stats, count_values, extract,
+    ColumnOptions, ColumnType, infer_schema, parse_number, project, resolve_column, resolve_columns,
+    sniff_delimiter, validate, NUMBER_SYMBOLS,
+};
+use encoding_rs::{Encoding, UTF_8};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use notify::{EventKind, RecursiveMode, Watcher};
+use owo_colors::{OwoColorize, Stream};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use sha2::{Digest, Sha256};
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    env,
     error::Error,
-    fs::{self, File},
-    io::{self, Write},
-    path::PathBuf,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Write},
+    iter,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        mpsc,
+        atomic::{self, AtomicBool},
+    },
+    time::{Duration, Instant},
 };
 
 #[derive(Parser)]
@@ -16,6 +40,62 @@ use std::{
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Disable colored output (also honors the NO_COLOR environment variable)
+    #[arg(long, global = true, default_value_t = false)]
+    no_color: bool,
+
+    /// Use plain ASCII labels instead of emoji (also honors the NO_EMOJI environment variable)
+    #[arg(long, global = true, default_value_t = false)]
+    ascii: bool,
+
+    /// Fail on rows whose field count differs from the header's instead of tolerating them
+    #[arg(long, global = true, default_value_t = false)]
+    strict: bool,
+
+    /// Detect whether input is comma, semicolon, tab or pipe separated
+    #[arg(long, global = true, default_value_t = false)]
+    auto_delimiter: bool,
+
+    /// Field delimiter of the input files: one character, or "tab" [default: ,]
+    #[arg(long, global = true, env = "CSV_TOOL_DELIMITER", value_name = "CHAR", value_parser = parse_delimiter)]
+    delimiter: Option<u8>,
+
+    /// Fail on any field longer than this many bytes, e.g. one swallowed by an unclosed quote
+    #[arg(long, global = true, value_name = "BYTES")]
+    max_field_size: Option<usize>,
+
+    /// Strip leading and trailing whitespace from every header and field as it is read
+    #[arg(long, global = true, default_value_t = false)]
+    trim: bool,
+
+    /// Match column names case-insensitively when no header matches exactly
+    #[arg(long, global = true, default_value_t = false)]
+    ignore_case_headers: bool,
+
+    /// Read numbers like $1,234.50 or 12% as numbers in stats, group-by and filter
+    #[arg(long, global = true, default_value_t = false)]
+    lenient_numbers: bool,
+
+    /// The symbols --lenient-numbers strips from either end of a number [default: $€£¥%]
+    #[arg(long, global = true, value_name = "CHARS", requires = "lenient_numbers")]
+    number_symbols: Option<String>,
+
+    /// Show a progress bar on stderr while reading input (only on a terminal)
+    #[arg(long, global = true, default_value_t = false)]
+    progress: bool,
+
+    /// Character encoding of the input files, e.g. latin1 or windows-1252
+    #[arg(long, global = true, value_name = "LABEL", value_parser = parse_encoding)]
+    encoding: Option<&'static Encoding>,
+
+    /// Character encoding to write CSV output in
+    #[arg(long, global = true, value_name = "LABEL", value_parser = parse_encoding)]
+    output_encoding: Option<&'static Encoding>,
+
+    /// Show the input line a parse error points at, with a caret under the problem (auto: only on a terminal)
+    #[arg(long, global = true, value_enum, value_name = "WHEN", default_value_t = When::Auto)]
+    pretty_errors: When,
 }
 
 #[derive(Subcommand)]
@@ -25,19 +105,38 @@ enum Commands {
         #[arg(short, long)]
         file: PathBuf,
 
-      
-        #[arg(short, long, default_value_t = 0)]
-        head: usize,
-
-        
-        #[arg(short, long, default_value_t = false)]
-        skip_header: bool,
+        #[command(flatten)]
+        options: ReadOptions,
     },
     
     Stats {
         /// CSV file path
         #[arg(short, long)]
         file: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// Show detailed statistics for just this column (name or index)
+        #[arg(short, long)]
+        column: Option<String>,
+
+        /// Number of most frequent values to list with --column
+        #[arg(long, default_value_t = 10, requires = "column")]
+        top: usize,
+
+        /// Treat this value as empty (repeatable, e.g. --null-value NA --null-value -)
+        #[arg(long = "null-value", allow_hyphen_values = true)]
+        null_values: Vec<String>,
+
+        /// Report how long the scan took and its rows per second
+        #[arg(long, default_value_t = false)]
+        timing: bool,
+
+        /// Keep running, recomputing the statistics whenever the file changes
+        #[arg(long, default_value_t = false)]
+        watch: bool,
     },
     /// Find rows matching a search term
     Find {
@@ -45,13 +144,16 @@ enum Commands {
         #[arg(short, long)]
         file: PathBuf,
 
-        /// Column to search in (name or index)
-        #[arg(short, long)]
-        column: String,
+        /// Column to search in (name or index); repeat with --term for several conditions
+        #[arg(short, long = "column", required = true)]
+        columns: Vec<String>,
 
-        /// Term to search for
-        #[arg(short, long)]
-        term: String,
+        /// Term to search for, paired with the --column in the same position
+        #[arg(short, long = "term", required = true)]
+        terms: Vec<String>,
+
+        #[command(flatten)]
+        options: FindOptions,
     },
     /// Extract specific columns from CSV
     Extract {
@@ -66,280 +168,2862 @@ enum Commands {
         /// Columns to extract (comma separated names or indices)
         #[arg(short, long)]
         columns: String,
+
+        /// Which header to use when a column name appears more than once (1-based)
+        #[arg(long)]
+        occurrence: Option<usize>,
+
+        #[command(flatten)]
+        range: RowRange,
+
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    /// Remove columns from CSV, keeping the rest in their original order
+    Drop {
+        /// Input CSV file path
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Output CSV file path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Columns to remove (comma separated names or indices)
+        #[arg(short, long)]
+        columns: String,
+
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    /// Redact sensitive columns before sharing a file
+    Mask {
+        /// Input CSV file path
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Output CSV file path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Columns to mask (comma separated names or indices)
+        #[arg(short, long)]
+        columns: String,
+
+        /// full: ****, partial: keep the first and last character,
+        /// hash: short SHA-256 so equal values still match
+        #[arg(long, value_enum, default_value_t = MaskMode::Full)]
+        mode: MaskMode,
+
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    /// Replace blank cells with a default value
+    Fill {
+        /// Input CSV file path
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Output CSV file path
+        #[arg(short, long, required_unless_present = "inplace")]
+        output: Option<PathBuf>,
+
+        /// Overwrite the input file instead of writing to --output
+        #[arg(long, conflicts_with = "output")]
+        inplace: bool,
+
+        /// Value to write into blank cells
+        #[arg(long, allow_hyphen_values = true)]
+        value: String,
+
+        /// Only fill these columns (comma separated names or indices)
+        #[arg(short, long)]
+        columns: Option<String>,
+
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    /// Rename header columns, passing the data rows through unchanged
+    RenameHeaders {
+        /// Input CSV file path
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Output CSV file path
+        #[arg(short, long, required_unless_present = "inplace")]
+        output: Option<PathBuf>,
+
+        /// Overwrite the input file instead of writing to --output
+        #[arg(long, conflicts_with = "output")]
+        inplace: bool,
+
+        /// Renames as old=new pairs, comma separated; old may be a name or index
+        #[arg(short, long)]
+        map: String,
+
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    /// Stack several CSV files with matching headers into one
+    Concat {
+        /// Input CSV file paths, in order
+        #[arg(short, long = "file", num_args = 1.., required = true)]
+        files: Vec<PathBuf>,
+
+        /// Output CSV file path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Append rows even when a file's headers differ from the first file's
+        #[arg(long, default_value_t = false)]
+        ignore_header_mismatch: bool,
+
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    /// Aggregate a column per distinct value of another column
+    GroupBy {
+        /// Input CSV file path
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Column to group rows by (name or index)
+        #[arg(short, long)]
+        by: String,
+
+        /// Column to aggregate (name or index); not needed for count
+        #[arg(short, long)]
+        agg: Option<String>,
+
+        /// Aggregate operation: count, sum, mean, min or max
+        #[arg(long, default_value = "count")]
+        op: Aggregate,
+
+        /// Output CSV file path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        #[command(flatten)]
+        counts: CountOptions,
+
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    /// Keep the rows matching an expression like "age > 30 and status == active"
+    Filter {
+        /// Input CSV file path
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Filter expression: comparisons (==, !=, <, <=, >, >=, contains)
+        /// joined with and/or, grouped with parentheses
+        #[arg(short = 'w', long = "where")]
+        expr: String,
+
+        /// Output CSV file path (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    /// Reshape long rows (key, name, value) into one row per key with a column per name
+    Pivot {
+        /// Input CSV file path
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Column identifying each output row (name or index)
+        #[arg(short, long)]
+        index: String,
+
+        /// Column whose values become the new headers (name or index)
+        #[arg(short, long)]
+        column: String,
+
+        /// Column holding the cell values (name or index)
+        #[arg(short, long)]
+        value: String,
+
+        /// Output CSV file path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// What to do when a key has two values for the same column
+        #[arg(long, value_enum, default_value_t = OnConflict::Error)]
+        on_conflict: OnConflict,
+
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    /// Count how often each value appears in a column, most frequent first
+    Uniq {
+        /// CSV file path
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Column to count (name or index)
+        #[arg(short, long)]
+        column: String,
+
+        /// Only show the N most frequent values
+        #[arg(long)]
+        top: Option<usize>,
+
+        /// Write the counts as CSV to this file instead of printing a table
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        counts: CountOptions,
+
+        #[command(flatten)]
+        write: WriteOptions,
+    },
+    /// Convert rows to JSON objects keyed by header
+    ToJson {
+        /// CSV file path
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Output JSON file path (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Indent the JSON array for reading
+        #[arg(long, default_value_t = false, conflicts_with = "ndjson")]
+        pretty: bool,
+
+        /// Write one object per line instead of an array, streaming as rows are read
+        #[arg(long, default_value_t = false)]
+        ndjson: bool,
+
+        /// Write numbers, booleans and nulls as JSON values, typed per column as `schema` infers them
+        #[arg(long, default_value_t = false)]
+        typed: bool,
+
+        /// Run everything but only report what would be written
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Infer a JSON Schema for the rows from the data
+    Schema {
+        /// CSV file path
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Output JSON file path (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// List the columns that are never empty as required properties
+        #[arg(long, default_value_t = false)]
+        required: bool,
+
+        /// Run everything but only report what would be written
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Print each row through a template, e.g. to generate SQL inserts or log lines
+    Format {
+        /// CSV file path
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Text with {column} placeholders (header names or 0-based indices); {{ and }} are literal braces
+        #[arg(short, long)]
+        template: String,
+
+        /// Output file path (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Run everything but only report what would be written
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Check that every row has one field per header and the required values
+    #[command(after_help = "Exit codes:\n  0  no problems found\n  1  data problems found\n  2  the file could not be read or parsed")]
+    Validate {
+        /// CSV file path
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// Columns that must not be empty (comma separated names or indices)
+        #[arg(short, long)]
+        required: Option<String>,
+
+        /// Print the report as JSON (rows_checked, problems with row, code and detail)
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Compare two versions of a file, matching rows by a key column
+    Diff {
+        /// The earlier version of the file
+        #[arg(long)]
+        old: PathBuf,
+
+        /// The later version of the file
+        #[arg(long)]
+        new: PathBuf,
+
+        /// Column identifying a row in both files (name or index)
+        #[arg(short, long)]
+        key: String,
+
+        /// Print the differences as JSON (added, removed, changed, added_columns, removed_columns)
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Print a shell completion script (bash, elvish, fish, powershell, zsh)
+    #[command(hide = true)]
+    Completions {
+        shell: Shell,
     },
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let cli = Cli::parse();
+/// What Read shows of the file.
+#[derive(Args)]
+struct ReadOptions {
+    /// Show only the first N rows (0 shows them all)
+    #[arg(short = 'n', long, default_value_t = 0)]
+    head: usize,
 
-    match &cli.command {
-        Commands::Read {
-            file,
-            head,
-            skip_header,
-        } => {
-            read_csv(file, *head, *skip_header)?;
-        }
-        Commands::Stats { file } => {
-            display_stats(file)?;
-        }
-        Commands::Find { file, column, term } => {
-            find_in_csv(file, column, term)?;
+    /// Show only the last N rows
+    #[arg(short, long, conflicts_with = "head")]
+    tail: Option<usize>,
+
+    /// Don't print the header row
+    #[arg(short, long, default_value_t = false)]
+    skip_header: bool,
+
+    /// Only show these columns (comma separated names or indices)
+    #[arg(short, long)]
+    columns: Option<String>,
+
+    /// Truncate fields longer than this many characters, ending them in "..." (at least 4)
+    #[arg(long, default_value_t = DEFAULT_COL_WIDTH, value_parser = RangedU64ValueParser::<usize>::new().range(4..))]
+    max_col_width: usize,
+
+    /// Show fields in full, however long
+    #[arg(long, default_value_t = false, conflicts_with = "max_col_width")]
+    no_truncate: bool,
+
+    /// Finish with the number of empty cells in each column
+    #[arg(long, default_value_t = false)]
+    summary: bool,
+
+    /// Show at most this many columns of a wide row (0 shows them all)
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_COLUMNS)]
+    max_columns: usize,
+
+    /// Pad columns to a common width, right-aligning numeric ones
+    #[arg(long, default_value_t = false)]
+    align: bool,
+
+    /// Draw a border around the aligned table
+    #[arg(long, default_value_t = false)]
+    border: bool,
+
+    /// Text between columns
+    #[arg(long, default_value = DEFAULT_SEPARATOR, conflicts_with = "border")]
+    separator: String,
+
+    /// Print the shown rows last first
+    #[arg(long, default_value_t = false)]
+    reverse: bool,
+
+    /// Leave out the row-number column
+    #[arg(long, default_value_t = false)]
+    no_line_numbers: bool,
+
+    #[command(flatten)]
+    range: RowRange,
+}
+
+/// A window of data rows, counted from 1 and inclusive at both ends.
+#[derive(Args, Clone, Copy, Default)]
+struct RowRange {
+    /// Start at this data row (1-based)
+    #[arg(long, value_name = "N")]
+    from: Option<usize>,
+
+    /// Stop after this data row (1-based, inclusive)
+    #[arg(long, value_name = "N")]
+    to: Option<usize>,
+}
+
+impl RowRange {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.from == Some(0) || self.to == Some(0) {
+            return Err("--from and --to count data rows from 1".into());
         }
-        Commands::Extract {
-            file,
-            output,
-            columns,
-        } => {
-            extract_columns(file, output, columns)?;
+        if let (Some(from), Some(to)) = (self.from, self.to)
+            && from > to
+        {
+            return Err(format!("--from {} is after --to {}", from, to).into());
         }
+        Ok(())
     }
 
-    Ok(())
+    fn contains(&self, row: usize) -> bool {
+        self.from.is_none_or(|from| row >= from) && self.to.is_none_or(|to| row <= to)
+    }
+
+    /// Whether `row` and everything after it fall beyond the window, so
+    /// reading can stop there.
+    fn is_past(&self, row: usize) -> bool {
+        self.to.is_some_and(|to| row > to)
+    }
 }
 
-fn read_csv(file: &PathBuf, head: usize, skip_header: bool) -> Result<(), Box<dyn Error>> {
-    let file = File::open(file)?;
-    let mut reader = ReaderBuilder::new()
-        .flexible(true)
-        .has_headers(true)
-        .from_reader(file);
+/// How Find reports its matches.
+#[derive(Args, Clone, Copy)]
+struct FindOptions {
+    /// Print only the number of matching rows
+    #[arg(long, default_value_t = false)]
+    count: bool,
 
-    let headers = reader.headers()?.clone();
-    let mut records: Vec<StringRecord> = Vec::new();
+    /// Print nothing; exit 0 if any row matches, 1 otherwise
+    #[arg(short, long, default_value_t = false, conflicts_with = "count")]
+    quiet: bool,
 
-    // Skip header if requested
-    if !skip_header {
-        print_record(&headers, 0, true)?;
-        println!("{}", "-".repeat(80));
+    /// Print at most this many matching rows
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Skip this many matching rows before printing
+    #[arg(long, default_value_t = 0)]
+    offset: usize,
+
+    /// Which header to use when the column name appears more than once (1-based)
+    #[arg(long)]
+    occurrence: Option<usize>,
+
+    /// Print only the first match and stop reading
+    #[arg(long, default_value_t = false, conflicts_with_all = ["count", "quiet", "limit", "offset", "last"])]
+    first: bool,
+
+    /// Print only the last match
+    #[arg(long, default_value_t = false, conflicts_with_all = ["count", "quiet", "limit", "offset"])]
+    last: bool,
+
+    /// With several --column/--term pairs, whether a row must match all of them or any
+    #[arg(long = "match", value_enum, default_value_t = MatchMode::All)]
+    mode: MatchMode,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum MatchMode {
+    All,
+    Any,
+}
+
+/// Options shared by the commands that count rows per distinct value.
+#[derive(Args)]
+struct CountOptions {
+    /// Fail once the column has more than N distinct values
+    #[arg(long, value_name = "N")]
+    max_groups: Option<usize>,
+
+    /// Add each count's share of the total as a percentage
+    #[arg(long, default_value_t = false)]
+    percent: bool,
+
+    /// Decimal places for --percent
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "percent")]
+    precision: usize,
+}
+
+impl CountOptions {
+    fn percent_of(&self, count: usize, total: usize) -> String {
+        let share = if total > 0 { count as f64 / total as f64 * 100.0 } else { 0.0 };
+        format!("{:.*}", self.precision, share)
     }
+}
 
-    // Read and store records
-    for (i, result) in reader.records().enumerate() {
-        let record = result?;
-        records.push(record.clone());
-        
-        // Print immediately if no head limit or within head limit
-        if head == 0 || i < head {
-            print_record(&record, i + 1, false)?;
-        }
+/// Options shared by every command that writes a CSV file.
+#[derive(Args)]
+struct WriteOptions {
+    /// Run everything but only report what would be written
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+
+    /// End lines with \r\n instead of \n
+    #[arg(long, default_value_t = false)]
+    crlf: bool,
+
+    /// Start the output with a UTF-8 byte order mark (for Excel)
+    #[arg(long, default_value_t = false)]
+    utf8_bom: bool,
+
+    /// Add the rows to the end of the output file instead of replacing it;
+    /// a file that already has a header row doesn't get a second one
+    #[arg(long, default_value_t = false)]
+    append: bool,
+}
+
+const DEFAULT_COL_WIDTH: usize = 20;
+/// Columns Read shows before cutting a wide row short.
+const DEFAULT_MAX_COLUMNS: usize = 20;
+const DEFAULT_SEPARATOR: &str = " | ";
+
+/// The `[csv_tool]` table of the shared config file. Flags and environment
+/// variables override these.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    delimiter: Option<String>,
+    no_color: bool,
+    number_symbols: Option<String>,
+}
+
+fn parse_delimiter(s: &str) -> Result<u8, String> {
+    match s {
+        "tab" | "\\t" => Ok(b'\t'),
+        _ if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+        _ => Err(format!("'{}' isn't a single ASCII character or \"tab\"", s)),
     }
+}
 
-    // Summary
-    println!("{}", "-".repeat(80));
-    println!("Total rows: {}", records.len());
+/// Set once at startup by `--ascii` / `NO_EMOJI`. Like the color override,
+/// it's process-wide because [`Icon`]s are formatted all over the output.
+static ASCII: AtomicBool = AtomicBool::new(false);
 
-    Ok(())
+/// The global flags, resolved once against the config file and passed to
+/// everything that reads input or writes output.
+struct Ctx {
+    delimiter: u8,
+    auto_delimiter: bool,
+    strict: bool,
+    trim: bool,
+    max_field_size: Option<usize>,
+    ignore_case_headers: bool,
+    /// What `--lenient-numbers` strips; `None` parses numbers strictly
+    number_symbols: Option<Vec<char>>,
+    progress: bool,
+    /// `None` for UTF-8
+    input_encoding: Option<&'static Encoding>,
+    /// `None` for UTF-8
+    output_encoding: Option<&'static Encoding>,
+    pretty_errors: bool,
+    /// Keeps informational notes off the terminal under `find --quiet`
+    quiet: bool,
+    /// The files read as CSV so far, with their delimiters, so a parse error
+    /// can be traced back to its line
+    inputs: RefCell<Vec<(PathBuf, u8)>>,
 }
 
-fn print_record(record: &StringRecord, row_num: usize, is_header: bool) -> Result<(), Box<dyn Error>> {
-    let row_indicator = if is_header { "H" } else { &row_num.to_string() };
-    
-    print!("{:>5} | ", row_indicator);
-    
-    for (i, field) in record.iter().enumerate() {
-        if i > 0 {
-            print!(" | ");
+impl Ctx {
+    /// Also applies the display flags, which are process-wide: colors and
+    /// ASCII labels.
+    fn from_cli(cli: &Cli) -> Result<Ctx, Box<dyn Error>> {
+        let config: Config = shared_config::load("csv_tool")?;
+        if cli.no_color || config.no_color {
+            owo_colors::set_override(false);
         }
-        
-        // Truncate long fields for display
-        if field.len() > 20 {
-            print!("{}...", &field[0..17]);
-        } else {
-            print!("{}", field);
+        if cli.ascii || env::var_os("NO_EMOJI").is_some_and(|v| !v.is_empty()) {
+            ASCII.store(true, atomic::Ordering::Relaxed);
         }
+
+        let delimiter = match (cli.delimiter, &config.delimiter) {
+            (Some(delimiter), _) => delimiter,
+            (None, Some(delimiter)) => {
+                parse_delimiter(delimiter).map_err(|e| format!("Invalid delimiter in the config file: {}", e))?
+            }
+            (None, None) => b',',
+        };
+        let utf8_as_none = |encoding: Option<&'static Encoding>| encoding.filter(|&encoding| encoding != UTF_8);
+        Ok(Ctx {
+            delimiter,
+            auto_delimiter: cli.auto_delimiter,
+            strict: cli.strict,
+            trim: cli.trim,
+            max_field_size: cli.max_field_size,
+            ignore_case_headers: cli.ignore_case_headers,
+            number_symbols: cli.lenient_numbers.then(|| match cli.number_symbols.as_ref().or(config.number_symbols.as_ref()) {
+                Some(symbols) => symbols.chars().collect(),
+                None => NUMBER_SYMBOLS.to_vec(),
+            }),
+            // A bar drawn into a pipe or log file is just noise
+            progress: cli.progress && io::stderr().is_terminal(),
+            input_encoding: utf8_as_none(cli.encoding),
+            output_encoding: utf8_as_none(cli.output_encoding),
+            pretty_errors: match cli.pretty_errors {
+                When::Auto => io::stderr().is_terminal(),
+                When::Always => true,
+                When::Never => false,
+            },
+            quiet: matches!(&cli.command, Commands::Find { options, .. } if options.quiet),
+            inputs: RefCell::new(Vec::new()),
+        })
     }
-    
-    println!();
-    Ok(())
-}
 
-fn display_stats(file: &PathBuf) -> Result<(), Box<dyn Error>> {
-    let file_content = fs::read_to_string(file)?;
-    let mut reader = ReaderBuilder::new()
-        .flexible(true)
-        .has_headers(true)
-        .from_reader(file_content.as_bytes());
+    /// How column specs given on the command line are resolved.
+    fn columns(&self) -> ColumnOptions {
+        ColumnOptions {
+            occurrence: None,
+            ignore_case: self.ignore_case_headers,
+            on_collision: Some(|warning| eprintln!("Warning: {}", warning)),
+        }
+    }
 
-    let headers = reader.headers()?.clone();
+    /// Passed on to [`parse_number`] wherever cells are read as numbers.
+    fn number_symbols(&self) -> Option<&[char]> {
+        self.number_symbols.as_deref()
+    }
+}
+
+/// How much of a file `--auto-delimiter` looks at.
+const SNIFF_BYTES: u64 = 8 * 1024;
+
+/// The decorations used in output, each with a plain ASCII fallback.
+#[derive(Clone, Copy)]
+enum Icon {
+    Stats,
+    Size,
+    Times,
+    Headers,
+    Empty,
+    Columns,
+    Numeric,
+    Search,
+    DryRun,
+    Success,
+    Warning,
+    Timing,
+}
+
+impl fmt::Display for Icon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (emoji, ascii) = match self {
+            Icon::Stats => ("📊", "[stats]"),
+            Icon::Size => ("📏", "[size]"),
+            Icon::Times => ("×", "x"),
+            Icon::Headers => ("🔤", "[headers]"),
+            Icon::Empty => ("📉", "[empty]"),
+            Icon::Columns => ("📋", "[columns]"),
+            Icon::Numeric => ("🔢", "[numeric]"),
+            Icon::Search => ("🔍", "[find]"),
+            Icon::DryRun => ("🔎", "[dry run]"),
+            Icon::Success => ("✅", "[ok]"),
+            // Most terminals draw these narrow, hence the padding
+            Icon::Warning => ("⚠️ ", "[warning]"),
+            Icon::Timing => ("⏱️ ", "[timing]"),
+        };
+        f.write_str(if ASCII.load(atomic::Ordering::Relaxed) { ascii } else { emoji })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum When {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OnConflict {
+    /// Stop with an error naming the row
+    Error,
+    /// Keep the value from the row that comes last
+    Last,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MaskMode {
+    Full,
+    Partial,
+    Hash,
+}
+
+impl MaskMode {
+    // Empty cells stay empty so missing data is still visible
+    fn apply(self, value: &str) -> String {
+        if value.is_empty() {
+            return String::new();
+        }
+        match self {
+            MaskMode::Full => "****".to_string(),
+            MaskMode::Partial => {
+                let chars: Vec<char> = value.chars().collect();
+                if chars.len() <= 2 {
+                    return "*".repeat(chars.len());
+                }
+                format!("{}{}{}", chars[0], "*".repeat(chars.len() - 2), chars[chars.len() - 1])
+            }
+            // 12 hex digits: short enough to read, long enough not to collide in practice
+            MaskMode::Hash => Sha256::digest(value.as_bytes())
+                .iter()
+                .take(6)
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let ctx = Ctx::from_cli(&cli).unwrap_or_else(|e| {
+        print_error(e.as_ref());
+        process::exit(1);
+    });
+    if let Err(e) = run(&cli, &ctx) {
+        print_error(e.as_ref());
+        if ctx.pretty_errors
+            && let Some(snippet) = error_snippet(&ctx, e.as_ref())
+        {
+            eprint!("{}", snippet);
+        }
+        process::exit(1);
+    }
+}
+
+fn print_error(e: &dyn Error) {
+    eprintln!("{} {}", "Error:".if_supports_color(Stream::Stderr, |s| s.red()), e);
+}
+
+/// For a CSV parse error, the input line it points at with a caret under the
+/// field at fault, like a compiler's source context. Only when a single
+/// regular file was read: stdin can't be read again, and with several inputs
+/// the position could be in any of them.
+fn error_snippet(ctx: &Ctx, e: &(dyn Error + 'static)) -> Option<String> {
+    let error = iter::successors(Some(e), |&e| e.source()).find_map(|e| e.downcast_ref::<csv::Error>())?;
+    let (pos, field) = match error.kind() {
+        // Too many fields points at the first extra one, too few at the end
+        csv::ErrorKind::UnequalLengths { pos: Some(pos), expected_len, len } => {
+            (pos, (len > expected_len).then_some(*expected_len as usize))
+        }
+        csv::ErrorKind::Utf8 { pos: Some(pos), err } => (pos, Some(err.field())),
+        csv::ErrorKind::Deserialize { pos: Some(pos), err } => (pos, err.field().map(|field| field as usize)),
+        _ => return None,
+    };
+    let (path, delimiter) = match ctx.inputs.borrow().as_slice() {
+        [input] => input.clone(),
+        _ => return None,
+    };
+    if is_stream(&path).unwrap_or(true) {
+        return None;
+    }
+
+    // Re-read through the same decoding, so lines match what was parsed
+    let mut reader = BufReader::new(decode(ctx, File::open(&path).ok()?));
+    let mut bytes = Vec::new();
+    for _ in 0..pos.line() {
+        bytes.clear();
+        if reader.read_until(b'\n', &mut bytes).ok()? == 0 {
+            return None;
+        }
+    }
+    let text = String::from_utf8_lossy(&bytes);
+    let line = text.trim_end_matches(['\r', '\n']);
+    let column = field.and_then(|field| field_start(line, delimiter, field)).unwrap_or(line.len());
+    // Tabs are kept so the caret lines up however wide the terminal draws them
+    let indent: String = line[..column].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+
+    let number = pos.line().to_string();
+    let gutter = " ".repeat(number.len());
+    let bar = "|".if_supports_color(Stream::Stderr, |s| s.blue()).to_string();
+    Some(format!(
+        "{}{} {}:{}\n{} {}\n{} {} {}\n{} {} {}{}\n",
+        gutter,
+        "-->".if_supports_color(Stream::Stderr, |s| s.blue()),
+        path.display(),
+        number,
+        gutter,
+        bar,
+        number.if_supports_color(Stream::Stderr, |s| s.blue()),
+        bar,
+        line,
+        gutter,
+        bar,
+        indent,
+        "^".if_supports_color(Stream::Stderr, |s| s.red()),
+    ))
+}
+
+/// The byte offset in `line` where field `field` starts, skipping delimiters
+/// inside quotes; `None` when the line has fewer fields.
+fn field_start(line: &str, delimiter: u8, field: usize) -> Option<usize> {
+    if field == 0 {
+        return Some(0);
+    }
+    let mut seen = 0;
+    let mut quoted = false;
+    for (idx, c) in line.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if !quoted && c == delimiter as char {
+            seen += 1;
+            if seen == field {
+                return Some(idx + c.len_utf8());
+            }
+        }
+    }
+    None
+}
+
+fn run(cli: &Cli, ctx: &Ctx) -> Result<(), Box<dyn Error>> {
+    match &cli.command {
+        Commands::Read { file, options } => {
+            read_csv(ctx, file, options)?;
+        }
+        Commands::Stats {
+            file,
+            format,
+            column,
+            top,
+            null_values,
+            timing,
+            watch,
+        } => {
+            let run = || match column {
+                Some(column) => display_column_stats(ctx, file, column, *top, *format, null_values, *timing),
+                None => display_stats(ctx, file, *format, null_values, *timing),
+            };
+            if *watch {
+                watch_file(file, run)?;
+            } else {
+                run()?;
+            }
+        }
+        Commands::Find {
+            file,
+            columns,
+            terms,
+            options,
+        } => {
+            if columns.len() != terms.len() {
+                return Err(format!("Got {} --column and {} --term values; each column needs one term", columns.len(), terms.len()).into());
+            }
+            let conditions: Vec<(&str, &str)> = columns.iter().map(String::as_str).zip(terms.iter().map(String::as_str)).collect();
+            let matched = find_in_csv(ctx, file, &conditions, options)?;
+            if options.quiet && !matched {
+                process::exit(1);
+            }
+        }
+        Commands::Extract {
+            file,
+            output,
+            columns,
+            occurrence,
+            range,
+            write,
+        } => {
+            extract_columns(ctx, file, output, columns, *occurrence, *range, write)?;
+        }
+        Commands::Drop {
+            file,
+            output,
+            columns,
+            write,
+        } => {
+            drop_columns(ctx, file, output, columns, write)?;
+        }
+        Commands::Mask {
+            file,
+            output,
+            columns,
+            mode,
+            write,
+        } => {
+            mask_columns(ctx, file, output, columns, *mode, write)?;
+        }
+        Commands::Fill {
+            file,
+            output,
+            inplace: _,
+            value,
+            columns,
+            write,
+        } => {
+            fill_blanks(ctx, file, output.as_deref(), value, columns.as_deref(), write)?;
+        }
+        Commands::RenameHeaders {
+            file,
+            output,
+            inplace: _,
+            map,
+            write,
+        } => {
+            rename_headers(ctx, file, output.as_deref(), map, write)?;
+        }
+        Commands::Concat {
+            files,
+            output,
+            ignore_header_mismatch,
+            write,
+        } => {
+            concat_csv(ctx, files, output, *ignore_header_mismatch, write)?;
+        }
+        Commands::GroupBy {
+            file,
+            by,
+            agg,
+            op,
+            output,
+            counts,
+            write,
+        } => {
+            group_by(ctx, file, by, (*op, agg.as_deref()), output, counts, write)?;
+        }
+        Commands::Pivot {
+            file,
+            index,
+            column,
+            value,
+            output,
+            on_conflict,
+            write,
+        } => {
+            pivot(ctx, file, [index, column, value], output, *on_conflict, write)?;
+        }
+        Commands::Filter {
+            file,
+            expr,
+            output,
+            write,
+        } => {
+            filter_csv(ctx, file, expr, output.as_deref(), write)?;
+        }
+        Commands::Uniq {
+            file,
+            column,
+            top,
+            output,
+            counts,
+            write,
+        } => {
+            uniq(ctx, file, column, *top, output.as_deref(), counts, write)?;
+        }
+        Commands::ToJson {
+            file,
+            output,
+            pretty,
+            ndjson,
+            typed,
+            dry_run,
+        } => {
+            to_json(ctx, file, output.as_deref(), *pretty, *ndjson, *typed, *dry_run)?;
+        }
+        Commands::Schema { file, output, required, dry_run } => {
+            write_schema(ctx, file, output.as_deref(), *required, *dry_run)?;
+        }
+        Commands::Format { file, template, output, dry_run } => {
+            format_rows(ctx, file, template, output.as_deref(), *dry_run)?;
+        }
+        Commands::Validate { file, required, json } => {
+            // Validate keeps data problems (1) apart from unreadable input (2)
+            match validate_csv(ctx, file, required.as_deref(), *json) {
+                Ok(true) => {}
+                Ok(false) => process::exit(1),
+                Err(e) => {
+                    print_error(e.as_ref());
+                    process::exit(2);
+                }
+            }
+        }
+        Commands::Diff { old, new, key, json } => {
+            diff_csv(ctx, old, new, key, *json)?;
+        }
+        Commands::Completions { shell } => {
+            clap_complete::generate(*shell, &mut Cli::command(), "csv_tool", &mut io::stdout());
+        }
+    }
+
+    Ok(())
+}
+
+/// Opens an input file, decoded to UTF-8 (see [`decode`]) and wrapped in a
+/// progress display under `--progress`.
+fn open_input(ctx: &Ctx, path: &Path) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    let file = File::open(path)?;
+    let input = if ctx.progress {
+        with_progress(file)?
+    } else {
+        Box::new(file)
+    };
+    Ok(decode(ctx, input))
+}
+
+/// Decodes input to UTF-8. A UTF-8 or UTF-16 byte order mark decides the
+/// encoding, even over `--encoding`, and is stripped. Without one the input
+/// is in the `--encoding` encoding, or passed through as UTF-8.
+fn decode<R: Read + 'static>(ctx: &Ctx, input: R) -> Box<dyn Read> {
+    Box::new(
+        DecodeReaderBytesBuilder::new()
+            .encoding(ctx.input_encoding)
+            .bom_override(true)
+            .strip_bom(true)
+            .build(input),
+    )
+}
+
+/// A bar over the file's size, or a spinner counting lines when the input
+/// has no known length (a pipe or `/dev/stdin`).
+fn with_progress(file: File) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    let metadata = file.metadata()?;
+    let (bar, count_lines) = if metadata.is_file() {
+        let bar = ProgressBar::with_draw_target(Some(metadata.len()), ProgressDrawTarget::stderr());
+        bar.set_style(ProgressStyle::with_template("{bar:40} {bytes}/{total_bytes} ({eta} left)")?);
+        (bar, false)
+    } else {
+        let bar = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr());
+        bar.set_style(ProgressStyle::with_template("{spinner} {pos} rows read")?);
+        (bar, true)
+    };
+    Ok(Box::new(ProgressReader { inner: file, bar, count_lines }))
+}
+
+/// Advances its bar by bytes read, or by lines read for a spinner, and
+/// clears it at end of input so it doesn't linger over the summary.
+struct ProgressReader<R> {
+    inner: R,
+    bar: ProgressBar,
+    count_lines: bool,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 {
+            self.bar.finish_and_clear();
+        } else if self.count_lines {
+            self.bar.inc(buf[..n].iter().filter(|&&b| b == b'\n').count() as u64);
+        } else {
+            self.bar.inc(n as u64);
+        }
+        Ok(n)
+    }
+}
+
+impl<R> Drop for ProgressReader<R> {
+    // Also covers readers abandoned early, like `find --first`
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("unknown encoding '{}'", label))
+}
+
+/// A CSV reader over `path` with the settings from [`reader_builder`] and
+/// any `--max-field-size` limit.
+fn csv_reader(ctx: &Ctx, path: &Path) -> Result<csv::Reader<Box<dyn Read>>, Box<dyn Error>> {
+    let delimiter = input_delimiter(ctx, path)?;
+    record_input(ctx, path, delimiter);
+    Ok(reader_builder(ctx, delimiter).from_reader(limit_fields(ctx, open_input(ctx, path)?, delimiter)))
+}
+
+/// Notes a file being read as CSV, for [`error_snippet`]. `--watch` reopens
+/// the same file, which is still one input.
+fn record_input(ctx: &Ctx, path: &Path, delimiter: u8) {
+    let mut inputs = ctx.inputs.borrow_mut();
+    if !inputs.iter().any(|(seen, _)| seen == path) {
+        inputs.push((path.to_path_buf(), delimiter));
+    }
+}
+
+/// Reader settings shared by the commands: a header row and ragged rows
+/// tolerated unless `--strict` is on.
+fn reader_builder(ctx: &Ctx, delimiter: u8) -> ReaderBuilder {
+    let mut builder = ReaderBuilder::new();
+    builder
+        .flexible(!ctx.strict)
+        .has_headers(true)
+        .delimiter(delimiter);
+    if ctx.trim {
+        builder.trim(Trim::All);
+    }
+    builder
+}
+
+/// The delimiter of `path`: under `--auto-delimiter` the one sniffed from
+/// the start of the file, otherwise `--delimiter`'s.
+fn input_delimiter(ctx: &Ctx, path: &Path) -> Result<u8, Box<dyn Error>> {
+    if ctx.auto_delimiter {
+        let mut sample = Vec::new();
+        decode(ctx, File::open(path)?.take(SNIFF_BYTES)).read_to_end(&mut sample)?;
+        let detected = sniff_delimiter(&String::from_utf8_lossy(&sample));
+        let delimiter = detected.unwrap_or(b',');
+
+        if !ctx.quiet {
+            let name = match delimiter {
+                b'\t' => "tab".to_string(),
+                other => format!("'{}'", other as char),
+            };
+            // Stderr, so piped CSV output stays clean
+            if detected.is_some() {
+                eprintln!("Detected delimiter {} in {}", name, path.display());
+            } else {
+                eprintln!("Couldn't detect the delimiter of {}; using {}", path.display(), name);
+            }
+        }
+        Ok(delimiter)
+    } else {
+        Ok(ctx.delimiter)
+    }
+}
+
+/// Wraps `input` so a field longer than `--max-field-size` stops the read
+/// with an error, before the CSV reader has buffered all of it.
+fn limit_fields(ctx: &Ctx, input: Box<dyn Read>, delimiter: u8) -> Box<dyn Read> {
+    match ctx.max_field_size {
+        Some(limit) => Box::new(FieldLimit::new(input, limit, delimiter)),
+        None => input,
+    }
+}
+
+/// Where [`FieldLimit`] is within the current field.
+#[derive(Clone, Copy, PartialEq)]
+enum FieldState {
+    Start,
+    Unquoted,
+    Quoted,
+    /// A quote inside a quoted field: the end of the field, or the first
+    /// half of an escaped `""`
+    QuoteInQuoted,
+}
+
+/// Follows the CSV quoting of the bytes passing through, just closely
+/// enough to measure each field and tell which row and column it is in.
+struct FieldLimit {
+    inner: Box<dyn Read>,
+    limit: usize,
+    delimiter: u8,
+    state: FieldState,
+    field_len: usize,
+    /// 0 for the header, then data rows from 1
+    row: usize,
+    column: usize,
+    /// Blank lines are skipped by the reader, so they don't count as rows
+    line_started: bool,
+    /// The header fields read so far, for naming a column in the error
+    header: Vec<Vec<u8>>,
+}
+
+impl FieldLimit {
+    fn new(inner: Box<dyn Read>, limit: usize, delimiter: u8) -> Self {
+        FieldLimit {
+            inner,
+            limit,
+            delimiter,
+            state: FieldState::Start,
+            field_len: 0,
+            row: 0,
+            column: 0,
+            line_started: false,
+            header: Vec::new(),
+        }
+    }
+
+    fn too_long(&self) -> io::Error {
+        let name = self.header.get(self.column).map(|name| String::from_utf8_lossy(name).into_owned());
+        let place = match (self.row, name) {
+            (0, _) => format!("the header, column {}", self.column + 1),
+            (row, Some(name)) => format!("row {}, column \"{}\"", row, name),
+            (row, None) => format!("row {}, column {}", row, self.column + 1),
+        };
+        io::Error::other(format!(
+            "A field in {} is longer than {} bytes (--max-field-size); is a quote left unclosed?",
+            place, self.limit
+        ))
+    }
+
+    fn end_field(&mut self) {
+        self.state = FieldState::Start;
+        self.field_len = 0;
+        self.column += 1;
+    }
+
+    fn end_row(&mut self) {
+        if self.line_started {
+            self.row += 1;
+        }
+        self.state = FieldState::Start;
+        self.field_len = 0;
+        self.column = 0;
+        self.line_started = false;
+    }
+}
+
+impl Read for FieldLimit {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        for &byte in &buf[..n] {
+            let outside_quotes = self.state != FieldState::Quoted;
+            if outside_quotes && byte == self.delimiter {
+                self.line_started = true;
+                self.end_field();
+                continue;
+            }
+            if outside_quotes && byte == b'\n' {
+                self.end_row();
+                continue;
+            }
+            if outside_quotes && byte == b'\r' {
+                continue;
+            }
+            self.line_started = true;
+            let content = match (self.state, byte) {
+                (FieldState::Start, b'"') => {
+                    self.state = FieldState::Quoted;
+                    false
+                }
+                (FieldState::Quoted, b'"') => {
+                    self.state = FieldState::QuoteInQuoted;
+                    false
+                }
+                (FieldState::QuoteInQuoted, b'"') => {
+                    self.state = FieldState::Quoted;
+                    true
+                }
+                (FieldState::Quoted, _) => true,
+                _ => {
+                    self.state = FieldState::Unquoted;
+                    true
+                }
+            };
+            if !content {
+                continue;
+            }
+            self.field_len += 1;
+            if self.row == 0 {
+                if self.header.len() <= self.column {
+                    self.header.resize(self.column + 1, Vec::new());
+                }
+                self.header[self.column].push(byte);
+            }
+            if self.field_len > self.limit {
+                return Err(self.too_long());
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// A ring buffer of the last `limit` items pushed, for `--tail`.
+struct LastRows<T> {
+    rows: VecDeque<T>,
+    limit: usize,
+}
+
+impl<T> LastRows<T> {
+    fn new(limit: usize) -> Self {
+        LastRows { rows: VecDeque::with_capacity(limit), limit }
+    }
+
+    fn push(&mut self, row: T) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.rows.len() == self.limit {
+            self.rows.pop_front();
+        }
+        self.rows.push_back(row);
+    }
+}
+
+fn read_csv(ctx: &Ctx, file: &Path, options: &ReadOptions) -> Result<(), Box<dyn Error>> {
+    let ReadOptions { head, tail, skip_header, summary, range, .. } = *options;
+    let format = RowFormat {
+        max_width: if options.no_truncate { None } else { Some(options.max_col_width) },
+        max_columns: (options.max_columns > 0).then_some(options.max_columns),
+        separator: &options.separator,
+        line_numbers: !options.no_line_numbers,
+        number_symbols: ctx.number_symbols(),
+    };
+    // Widths aren't known until every shown row is in, so an aligned or
+    // reversed table holds its rows back
+    let aligned = options.align || options.border;
+    let held = aligned || options.reverse;
+    let mut table: Vec<(usize, StringRecord)> = Vec::new();
+    range.check()?;
+    let mut reader = csv_reader(ctx, file)?;
+
+    let mut headers = reader.headers()?.clone();
+    let column_indices = match &options.columns {
+        Some(columns) => Some(resolve_columns(&headers, columns, ctx.columns())?),
+        None => None,
+    };
+    if let Some(indices) = &column_indices {
+        headers = project(&headers, indices);
+    }
+    // Skip header if requested
+    if !skip_header && !aligned {
+        print_record(&headers, 0, true, &format)?;
+        print_separator();
+    }
+
+    // Rows stream straight through; only a --tail needs to hold any back,
+    // and then never more than the last `tail` of them
+    let mut last_rows = LastRows::new(tail.unwrap_or(0));
+    // Rows in the --from/--to window; `row_num` is the position in the file
+    let mut total = 0;
+    let mut row_num = 0;
+    // Empty cells per displayed column, over every row rather than just the shown ones
+    let mut empty_cells = vec![0; headers.len()];
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        row_num += 1;
+        if range.is_past(row_num) {
+            break;
+        }
+        if !range.contains(row_num) {
+            continue;
+        }
+        total += 1;
+        if summary {
+            for (pos, count) in empty_cells.iter_mut().enumerate() {
+                let idx = column_indices.as_ref().map_or(pos, |indices| indices[pos]);
+                if record.get(idx).unwrap_or("").is_empty() {
+                    *count += 1;
+                }
+            }
+        }
+        let shown = match tail {
+            Some(tail) => tail > 0,
+            None => head == 0 || total <= head,
+        };
+        if !shown {
+            continue;
+        }
+
+        let row = match &column_indices {
+            Some(indices) => project(&record, indices),
+            None => record.clone(),
+        };
+        match tail {
+            Some(_) => last_rows.push((row_num, row)),
+            None if held => table.push((row_num, row)),
+            None => print_record(&row, row_num, false, &format)?,
+        }
+    }
+    table.extend(last_rows.rows);
+    if options.reverse {
+        table.reverse();
+    }
+    if aligned {
+        print_table((!skip_header).then_some(&headers), &table, &format, options.border);
+    } else {
+        for (row_num, row) in &table {
+            print_record(row, *row_num, false, &format)?;
+        }
+    }
+
+    // Summary
+    if !options.border {
+        print_separator();
+    }
+    println!("Total rows: {}", total);
+
+    if summary {
+        println!("Empty cells:");
+        for (name, count) in headers.iter().zip(&empty_cells) {
+            let pct = if total > 0 { *count as f64 / total as f64 * 100.0 } else { 0.0 };
+            println!("  {}: {} ({:.1}%)", name, count, pct);
+        }
+    }
+
+    Ok(())
+}
+
+/// Where a writing command sends its CSV: the output file (or stdout when
+/// there is none), or nowhere under `--dry-run`. Either way the bytes are counted for the summary.
+struct OutputSink {
+    inner: Box<dyn Write>,
+    bytes: usize,
+    /// Re-encodes the UTF-8 written to the sink under `--output-encoding`
+    encoding: Option<&'static Encoding>,
+    /// The start of a UTF-8 sequence split across two writes
+    pending: Vec<u8>,
+    /// Under `--inplace`, the temp file being written and the file it replaces
+    replace: Option<(PathBuf, PathBuf)>,
+    /// Under `--append`, the header row already in the output file
+    existing_header: Option<StringRecord>,
+}
+
+impl OutputSink {
+    /// Moves an `--inplace` temp file over the input; a no-op otherwise.
+    /// Call once everything has been written and flushed.
+    fn commit(&mut self) -> io::Result<()> {
+        if let Some((tmp, target)) = self.replace.take() {
+            // Close the temp file before renaming it
+            self.inner = Box::new(io::sink());
+            fs::rename(tmp, target)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for OutputSink {
+    // An uncommitted temp file means the command failed partway through
+    fn drop(&mut self) {
+        if let Some((tmp, _)) = self.replace.take() {
+            self.inner = Box::new(io::sink());
+            let _ = fs::remove_file(tmp);
+        }
+    }
+}
+
+impl Write for OutputSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(encoding) = self.encoding else {
+            let written = self.inner.write(buf)?;
+            self.bytes += written;
+            return Ok(written);
+        };
+
+        self.pending.extend_from_slice(buf);
+        let complete = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        let text = std::str::from_utf8(&self.pending[..complete]).expect("checked above");
+        // Characters the encoding can't represent become &#NNNN; references
+        let (encoded, _, _) = encoding.encode(text);
+        self.inner.write_all(&encoded)?;
+        self.bytes += encoded.len();
+        self.pending.drain(..complete);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn create_writer(ctx: &Ctx, output: Option<&Path>, options: &WriteOptions, flexible: bool) -> Result<Writer<OutputSink>, Box<dyn Error>> {
+    build_writer(ctx, output, options, flexible, None)
+}
+
+fn build_writer(
+    ctx: &Ctx,
+    output: Option<&Path>,
+    options: &WriteOptions,
+    flexible: bool,
+    replace: Option<(PathBuf, PathBuf)>,
+) -> Result<Writer<OutputSink>, Box<dyn Error>> {
+    let encoding = ctx.output_encoding;
+    if options.utf8_bom && encoding.is_some() {
+        return Err("--utf8-bom only applies to UTF-8 output".into());
+    }
+    let (existing_header, unterminated) = match output {
+        Some(output) if options.append => existing_header(output)?,
+        None if options.append => return Err("--append needs an output file".into()),
+        _ => (None, false),
+    };
+    let inner: Box<dyn Write> = match output {
+        _ if options.dry_run => Box::new(io::sink()),
+        Some(output) if options.append => Box::new(OpenOptions::new().create(true).append(true).open(output)?),
+        Some(output) => Box::new(File::create(output)?),
+        None => Box::new(io::stdout()),
+    };
+    let mut sink = OutputSink { inner, bytes: 0, encoding, pending: Vec::new(), replace, existing_header };
+
+    // The BOM goes in ahead of the csv writer so it precedes the header row
+    if options.utf8_bom && sink.existing_header.is_none() {
+        sink.write_all(b"\xEF\xBB\xBF")?;
+    }
+
+    // Terminator::default() is CRLF, so plain \n has to be asked for
+    let terminator = if options.crlf { Terminator::CRLF } else { Terminator::Any(b'\n') };
+    // Appended rows must start on a line of their own
+    if unterminated {
+        sink.write_all(if options.crlf { b"\r\n" } else { b"\n" })?;
+    }
+    Ok(WriterBuilder::new()
+        .flexible(flexible)
+        .terminator(terminator)
+        .from_writer(sink))
+}
+
+/// Whether `path` is stdin, a pipe or similar rather than a regular file.
+/// /dev/stdin redirected from a file looks like a regular file, so paths
+/// like it are recognised by name.
+fn is_stream(path: &Path) -> io::Result<bool> {
+    if path == Path::new("-") || path.starts_with("/dev") || path.starts_with("/proc") {
+        return Ok(true);
+    }
+    Ok(!fs::metadata(path)?.is_file())
+}
+
+/// The header row of a file `--append` is about to extend, if it has one,
+/// and whether its last line is missing its line ending. The file is read as
+/// this tool writes it, comma-separated, whatever the input delimiter is.
+fn existing_header(path: &Path) -> Result<(Option<StringRecord>, bool), Box<dyn Error>> {
+    let data = match fs::read(path) {
+        Ok(data) if !data.is_empty() => data,
+        Ok(_) => return Ok((None, false)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((None, false)),
+        Err(e) => return Err(e.into()),
+    };
+    let header = ReaderBuilder::new().from_reader(data.as_slice()).headers()?.clone();
+    Ok((Some(header), !data.ends_with(b"\n")))
+}
+
+/// Writes the header row, unless `--append` is adding to a file that already
+/// has one. Then the two must match, or the new rows would land under the
+/// wrong columns.
+fn write_header<T: AsRef<str>>(writer: &mut Writer<OutputSink>, header: impl IntoIterator<Item = T>) -> Result<(), Box<dyn Error>> {
+    let header: StringRecord = header.into_iter().collect();
+    match &writer.get_ref().existing_header {
+        None => writer.write_record(&header)?,
+        Some(existing) if *existing == header => {}
+        Some(existing) => {
+            let names = |record: &StringRecord| record.iter().collect::<Vec<_>>().join(",");
+            return Err(format!("Can't append: the output's header ({}) differs from this one ({})",
+                names(existing), names(&header)).into());
+        }
+    }
+    Ok(())
+}
+
+/// A writer for `--inplace`: output goes to a temp file beside `input`, which
+/// only replaces it once the command calls [`OutputSink::commit`]. Failing
+/// before that leaves `input` as it was.
+fn create_in_place_writer(ctx: &Ctx, input: &Path, options: &WriteOptions, flexible: bool) -> Result<Writer<OutputSink>, Box<dyn Error>> {
+    // Stdin or a pipe can't be renamed over
+    if is_stream(input)? {
+        return Err(format!("--inplace needs a regular file, and {} isn't one", input.display()).into());
+    }
+    if options.append {
+        return Err("--append can't be combined with --inplace".into());
+    }
+    if options.dry_run {
+        return create_writer(ctx, Some(input), options, flexible);
+    }
+
+    let name = input.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = input.with_file_name(format!(".{}.tmp", name));
+    build_writer(ctx, Some(&tmp), options, flexible, Some((tmp.clone(), input.to_path_buf())))
+}
+
+/// The sink for a command that writes text rather than CSV, such as JSON:
+/// the output file or stdout, buffered, or nowhere under `--dry-run`.
+fn text_output(output: Option<&Path>, dry_run: bool) -> io::Result<OutputSink> {
+    let inner: Box<dyn Write> = match output {
+        _ if dry_run => Box::new(io::sink()),
+        Some(output) => Box::new(BufWriter::new(File::create(output)?)),
+        None => Box::new(BufWriter::new(io::stdout())),
+    };
+    Ok(OutputSink { inner, bytes: 0, encoding: None, pending: Vec::new(), replace: None, existing_header: None })
+}
+
+fn report_dry_run(output: Option<&Path>, rows: usize, sink: &OutputSink) {
+    println!("{} Dry run: would write {} rows ({} bytes) to {}", Icon::DryRun, 
+        rows, 
+        sink.bytes, 
+        output.map_or("stdout".into(), |output| output.display().to_string()));
+}
+
+/// Whether owo-colors styles stdout, going by its own terminal, `NO_COLOR`
+/// and `--no-color` checks.
+fn stdout_colored() -> bool {
+    "x".if_supports_color(Stream::Stdout, |s| s.bold()).to_string() != "x"
+}
+
+fn print_separator() {
+    println!("{}", "-".repeat(80).if_supports_color(Stream::Stdout, |s| s.dimmed()));
+}
+
+fn print_success(message: &str) {
+    println!("{}", message.if_supports_color(Stream::Stdout, |s| s.green()));
+}
+
+/// How rows are laid out for display.
+struct RowFormat<'a> {
+    /// Truncate fields longer than this many characters
+    max_width: Option<usize>,
+    /// Show this many fields of a wider row and a count of the rest
+    max_columns: Option<usize>,
+    separator: &'a str,
+    /// Start each row with its number, or "H" for the header
+    line_numbers: bool,
+    /// Passed on to [`parse_number`] when deciding which columns to right-align
+    number_symbols: Option<&'a [char]>,
+}
+
+impl Default for RowFormat<'_> {
+    fn default() -> Self {
+        RowFormat {
+            max_width: Some(DEFAULT_COL_WIDTH),
+            max_columns: None,
+            separator: DEFAULT_SEPARATOR,
+            line_numbers: true,
+            number_symbols: None,
+        }
+    }
+}
+
+impl RowFormat<'_> {
+    // The fields as displayed, ending with an "... (N more)" cell when the
+    // row is cut short
+    fn cells(&self, record: &StringRecord) -> Vec<String> {
+        let shown = self.max_columns.unwrap_or(usize::MAX);
+        let mut cells: Vec<String> = record
+            .iter()
+            .take(shown)
+            .map(|field| match self.max_width {
+                // Counting chars rather than bytes
+                Some(width) if field.chars().count() > width => {
+                    let keep = width.saturating_sub(3);
+                    format!("{}...", field.chars().take(keep).collect::<String>())
+                }
+                _ => field.to_string(),
+            })
+            .collect();
+        if record.len() > shown {
+            cells.push(format!("... ({} more)", record.len() - shown));
+        }
+        cells
+    }
+}
+
+/// Prints one row of the table.
+fn print_record(record: &StringRecord, row_num: usize, is_header: bool, format: &RowFormat) -> Result<(), Box<dyn Error>> {
+    let cells = format.cells(record).join(format.separator);
+    if format.line_numbers {
+        let row_indicator = if is_header { "H" } else { &row_num.to_string() };
+        println!("{:>5}{}{}", row_indicator, format.separator, cells);
+    } else {
+        println!("{}", cells);
+    }
+    Ok(())
+}
+
+/// Prints rows with every column padded to its widest cell. Columns whose
+/// non-empty cells are all numbers (by the same test stats uses) are
+/// right-aligned.
+fn print_table(header: Option<&StringRecord>, rows: &[(usize, StringRecord)], format: &RowFormat, border: bool) {
+    let header = header.map(|header| ("H".to_string(), format.cells(header)));
+    let body: Vec<(String, Vec<String>)> = rows.iter().map(|(num, row)| (num.to_string(), format.cells(row))).collect();
+
+    let columns = header.iter().chain(&body).map(|(_, cells)| cells.len()).max().unwrap_or(0);
+    let mut widths = vec![0; columns];
+    for (_, cells) in header.iter().chain(&body) {
+        for (width, cell) in widths.iter_mut().zip(cells) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let numeric: Vec<bool> = (0..columns)
+        .map(|i| {
+            let mut values = body.iter().filter_map(|(_, cells)| cells.get(i)).filter(|cell| !cell.is_empty()).peekable();
+            values.peek().is_some() && values.all(|cell| parse_number(cell, format.number_symbols).is_some())
+        })
+        .collect();
+
+    let ascii = ASCII.load(atomic::Ordering::Relaxed);
+    let (horizontal, vertical) = if ascii { ("-", "|") } else { ("─", "│") };
+    let separator = if border { format!(" {} ", vertical) } else { format.separator.to_string() };
+    let line = |label: &str, cells: &[String]| {
+        let padded: Vec<String> = (0..columns)
+            .map(|i| {
+                let cell = cells.get(i).map_or("", String::as_str);
+                if numeric[i] {
+                    format!("{:>1$}", cell, widths[i])
+                } else {
+                    format!("{:<1$}", cell, widths[i])
+                }
+            })
+            .collect();
+        let mut line = padded.join(&separator);
+        if format.line_numbers {
+            line = format!("{:>5}{}{}", label, separator, line);
+        }
+        if border {
+            println!("{} {} {}", vertical, line, vertical);
+        } else {
+            println!("{}", line.trim_end());
+        }
+    };
+    // The row-number column is 5 wide, like the unaligned output
+    let number_width = format.line_numbers.then_some(5);
+    let rule = |[left, middle, right]: [&str; 3]| {
+        let segments: Vec<String> =
+            number_width.into_iter().chain(widths.iter().copied()).map(|w| horizontal.repeat(w + 2)).collect();
+        println!("{}{}{}", left, segments.join(middle), right);
+    };
+    let corners = |fancy| if ascii { ["+"; 3] } else { fancy };
+
+    if border {
+        rule(corners(["┌", "┬", "┐"]));
+    }
+    if let Some((label, cells)) = &header {
+        line(label, cells);
+        if border {
+            rule(corners(["├", "┼", "┤"]));
+        } else {
+            print_separator();
+        }
+    }
+    for (label, cells) in &body {
+        line(label, cells);
+    }
+    if border {
+        rule(corners(["└", "┴", "┘"]));
+    }
+}
+
+/// How long a burst of change events has to die down before `--watch`
+/// reruns, so one save doesn't trigger several recomputes.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Runs `run` now and again after each change to `path`, until interrupted.
+/// The file's directory is watched rather than the file itself, so a file
+/// replaced by renaming a new one over it is still followed.
+fn watch_file(path: &Path, mut run: impl FnMut() -> Result<(), Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+    if is_stream(path)? {
+        return Err(format!("--watch needs a regular file, and {} isn't one", path.display()).into());
+    }
+    let path = path.canonicalize()?;
+    let dir = path.parent().ok_or("--watch needs a file inside a directory")?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+    loop {
+        if io::stdout().is_terminal() {
+            print!("\x1B[2J\x1B[H");
+        }
+        // A file caught halfway through being written is reported, and the
+        // next change will bring a fresh run
+        if let Err(e) = run() {
+            print_error(e.as_ref());
+        }
+        println!();
+        println!("Watching {} for changes (Ctrl-C to stop)", path.display());
+        io::stdout().flush()?;
+
+        // Reading the file for the run raises access events of its own
+        loop {
+            let event = rx.recv()??;
+            let changed = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_));
+            if changed && event.paths.contains(&path) {
+                break;
+            }
+        }
+        while rx.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+    }
+}
+
+fn display_stats(ctx: &Ctx, file: &Path, format: OutputFormat, null_values: &[String], timing: bool) -> Result<(), Box<dyn Error>> {
+    let started = Instant::now();
+    let mut reader = csv_reader(ctx, file)?;
+
+    let headers = reader.headers()?.clone();
+    let stats = compute_stats(&mut reader, null_values, ctx.number_symbols())?;
+    let elapsed = started.elapsed();
+
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        if timing {
+            report_timing(elapsed, stats.row_count, format);
+        }
+        return Ok(());
+    }
+
+    let row_count = stats.row_count;
+    let column_count = stats.column_count();
+    let empty_cells = stats.empty_cells;
+    
+    // Print the statistics
+    println!("{} CSV File Statistics: {}", Icon::Stats, file.display());
+    println!("---------------------------------------------------");
+    println!("{} Dimensions: {} rows {} {} columns", Icon::Size, row_count, Icon::Times, column_count);
+    println!("{} Headers: {}", Icon::Headers, headers.iter().collect::<Vec<_>>().join(", "));
+    println!("{} Empty cells: {} ({:.2}%)", Icon::Empty, 
+        empty_cells, 
+        (empty_cells as f64 / (row_count * column_count) as f64) * 100.0
+    );
+    println!();
     
-    // Get basic stats
-    let mut row_count = 0;
-    let mut column_count = headers.len();
-    let mut empty_cells = 0;
-    let mut column_stats: Vec<HashMap<String, usize>> = vec![HashMap::new(); column_count];
+    // Print column-specific stats
+    println!("{} Column Statistics:", Icon::Columns);
+    for (i, col_stats) in stats.columns.iter().enumerate() {
+        println!("  {} [{}]:", i + 1, col_stats.name);
+        println!("    - Unique values: {}", col_stats.unique_values());
+        if let Some((val, count)) = col_stats.most_common() {
+            println!("    - Most common: \"{}\" ({} times, {:.1}%)", 
+                val,
+                count,
+                (count as f64 / row_count as f64) * 100.0
+            );
+        }
+        if let Some(numeric) = &col_stats.numeric {
+            println!("    - Mean: {:.2}, Median: {:.2} (P25: {:.2}, P75: {:.2})",
+                numeric.mean,
+                numeric.median,
+                numeric.p25,
+                numeric.p75
+            );
+        }
+    }
     
+    if timing {
+        report_timing(elapsed, row_count, format);
+    }
+
+    Ok(())
+}
+
+fn display_column_stats(
+    ctx: &Ctx,
+    file: &Path,
+    column: &str,
+    top: usize,
+    format: OutputFormat,
+    null_values: &[String],
+    timing: bool,
+) -> Result<(), Box<dyn Error>> {
+    let started = Instant::now();
+    let mut reader = csv_reader(ctx, file)?;
+
+    let headers = reader.headers()?.clone();
+    let column_index = resolve_column(&headers, column, ctx.columns())?;
+    let stats = compute_column_stats(&mut reader, column_index, null_values, ctx.number_symbols())?;
+    let elapsed = started.elapsed();
+
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        if timing {
+            report_timing(elapsed, stats.rows, format);
+        }
+        return Ok(());
+    }
+
+    let row_count = stats.rows;
+
+    println!("{} Column Statistics: {} [{}]", Icon::Stats, file.display(), stats.name);
+    println!("---------------------------------------------------");
+    println!("{} Rows: {}", Icon::Size, row_count);
+    println!("{} Unique values: {}", Icon::Headers, stats.unique_values());
+    println!("{} Empty cells: {} ({:.2}%)", Icon::Empty, 
+        stats.empty_cells, 
+        (stats.empty_cells as f64 / row_count as f64) * 100.0
+    );
+    if let Some(numeric) = &stats.numeric {
+        println!("{} Numeric: count {}, sum {}, min {}, max {}", Icon::Numeric, 
+            numeric.count, numeric.sum, numeric.min, numeric.max);
+        println!("    - Mean: {:.2}, Median: {:.2} (P25: {:.2}, P75: {:.2})",
+            numeric.mean,
+            numeric.median,
+            numeric.p25,
+            numeric.p75
+        );
+    }
+    println!();
+
+    println!("{} Top {} values:", Icon::Columns, top);
+    for (val, count) in stats.top_values(top) {
+        println!("  \"{}\": {} ({:.1}%)", 
+            val,
+            count,
+            (count as f64 / row_count as f64) * 100.0
+        );
+    }
+
+    if timing {
+        report_timing(elapsed, row_count, format);
+    }
+
+    Ok(())
+}
+
+// Keeps JSON on stdout parseable by sending the timing to stderr.
+fn report_timing(elapsed: Duration, rows: usize, format: OutputFormat) {
+    let seconds = elapsed.as_secs_f64();
+    let rate = if seconds > 0.0 { rows as f64 / seconds } else { 0.0 };
+    let line = format!("{} Scanned {} rows in {:.3}s ({:.0} rows/s)", Icon::Timing, rows, seconds, rate);
+    if format == OutputFormat::Json {
+        eprintln!("{}", line);
+    } else {
+        println!();
+        println!("{}", line);
+    }
+}
+
+/// Searches for rows whose `(column, term)` conditions hold, each one a
+/// case-insensitive substring match, combined by `options.mode`.
+fn find_in_csv(ctx: &Ctx, file: &Path, conditions: &[(&str, &str)], options: &FindOptions) -> Result<bool, Box<dyn Error>> {
+    let FindOptions { count, quiet, limit, offset, occurrence, first, last, mode } = *options;
+    let mut reader = csv_reader(ctx, file)?;
+    
+    let headers = reader.headers()?.clone();
+    let mut checks = Vec::new();
+    for &(column, term) in conditions {
+        checks.push((resolve_column(&headers, column, ColumnOptions { occurrence, ..ctx.columns() })?, term.to_lowercase()));
+    }
+    let verbose = !count && !quiet;
+    
+    if verbose {
+        let described: Vec<String> = conditions
+            .iter()
+            .zip(&checks)
+            .map(|((_, term), (idx, _))| format!("\"{}\" in column \"{}\"", term, &headers[*idx]))
+            .collect();
+        let joiner = if mode == MatchMode::All { " and " } else { " or " };
+        println!("{} Searching for {}:", Icon::Search, described.join(joiner));
+        print_separator();
+        
+        // Print headers
+        print_record(&headers, 0, true, &RowFormat::default())?;
+        print_separator();
+    }
+    
+    let mut matches = 0;
+    // Every match is counted, but only those inside [offset, offset + limit) are printed
+    let end = if last { 0 } else { limit.map_or(usize::MAX, |limit| offset.saturating_add(limit)) };
+    let mut last_match = None;
+    
+    for (row_idx, result) in reader.records().enumerate() {
+        let record = result?;
+        
+        let holds = |(idx, term): &(usize, String)| record.get(*idx).is_some_and(|field| field.to_lowercase().contains(term));
+        let is_match = match mode {
+            MatchMode::All => checks.iter().all(holds),
+            MatchMode::Any => checks.iter().any(holds),
+        };
+        if is_match {
+            // One match is enough to answer a quiet search
+            if quiet {
+                return Ok(true);
+            }
+            if verbose && matches >= offset && matches < end {
+                print_record(&record, row_idx + 1, false, &RowFormat::default())?;
+            }
+            matches += 1;
+            if first {
+                break;
+            }
+            if last {
+                last_match = Some((row_idx + 1, record));
+            }
+        }
+    }
+    if let Some((row_num, record)) = &last_match {
+        print_record(record, *row_num, false, &RowFormat::default())?;
+    }
+    
+    if count {
+        println!("{}", matches);
+    } else if verbose {
+        print_separator();
+        let shown = matches.min(end).saturating_sub(offset);
+        if first && matches > 0 {
+            println!("Found a matching row (stopped at the first)");
+        } else if last && matches > 1 {
+            println!("Found {} matching rows (showing the last)", matches);
+        } else if !last && shown < matches {
+            println!("Found {} matching rows (showing {})", matches, shown);
+        } else {
+            println!("Found {} matching rows", matches);
+        }
+    }
+    
+    Ok(matches > 0)
+}
+
+fn extract_columns(
+    ctx: &Ctx,
+    input: &Path,
+    output: &Path,
+    columns: &str,
+    occurrence: Option<usize>,
+    range: RowRange,
+    write: &WriteOptions,
+) -> Result<(), Box<dyn Error>> {
+    range.check()?;
+
+    // Open the input file
+    let mut reader = csv_reader(ctx, input)?;
+    
+    let headers = reader.headers()?.clone();
+    
+    // Resolve column indices
+    let column_indices = resolve_columns(&headers, columns, ColumnOptions { occurrence, ..ctx.columns() })?;
+    
+    let Some(count) = write_columns(ctx, &mut reader, &headers, &column_indices, range, output, write)? else {
+        return Ok(());
+    };
+    
+    print_success(&format!("{} Successfully extracted {} columns to {}", Icon::Success, 
+        column_indices.len(), 
+        output.display()));
+    println!("   Processed {} rows", count);
+    
+    Ok(())
+}
+
+fn mask_columns(ctx: &Ctx, input: &Path, output: &Path, columns: &str, mode: MaskMode, write: &WriteOptions) -> Result<(), Box<dyn Error>> {
+    let mut reader = csv_reader(ctx, input)?;
+    let headers = reader.headers()?.clone();
+    let targets = resolve_columns(&headers, columns, ctx.columns())?;
+
+    let mut writer = create_writer(ctx, Some(output), write, true)?;
+    write_header(&mut writer, &headers)?;
+
+    let mut rows = 0;
+    for result in reader.records() {
+        let record = result?;
+        let row: StringRecord = record
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| if targets.contains(&idx) { mode.apply(field) } else { field.to_string() })
+            .collect();
+        writer.write_record(&row)?;
+        rows += 1;
+    }
+
+    writer.flush()?;
+
+    if write.dry_run {
+        report_dry_run(Some(output), rows, writer.get_ref());
+        return Ok(());
+    }
+
+    print_success(&format!("{} Successfully masked {} columns in {}", Icon::Success,
+        targets.len(),
+        output.display()));
+    println!("   Processed {} rows", rows);
+
+    Ok(())
+}
+
+/// With no `output`, fills `input` in place.
+fn fill_blanks(
+    ctx: &Ctx,
+    input: &Path,
+    output: Option<&Path>,
+    value: &str,
+    columns: Option<&str>,
+    write: &WriteOptions,
+) -> Result<(), Box<dyn Error>> {
+    let mut reader = csv_reader(ctx, input)?;
+    let headers = reader.headers()?.clone();
+
+    let targets = match columns {
+        Some(columns) => resolve_columns(&headers, columns, ctx.columns())?,
+        None => (0..headers.len()).collect(),
+    };
+
+    let mut writer = match output {
+        Some(output) => create_writer(ctx, Some(output), write, true)?,
+        None => create_in_place_writer(ctx, input, write, true)?,
+    };
+    let output = output.unwrap_or(input);
+    write_header(&mut writer, &headers)?;
+
+    let mut rows = 0;
+    let mut filled = 0;
     for result in reader.records() {
         let record = result?;
-        row_count += 1;
-        
-        for (i, field) in record.iter().enumerate() {
-            if field.is_empty() {
-                empty_cells += 1;
+        let row: StringRecord = record
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| {
+                if field.trim().is_empty() && targets.contains(&idx) {
+                    filled += 1;
+                    value
+                } else {
+                    field
+                }
+            })
+            .collect();
+        writer.write_record(&row)?;
+        rows += 1;
+    }
+
+    let mut sink = writer.into_inner().map_err(|e| e.into_error())?;
+    sink.commit()?;
+
+    if write.dry_run {
+        report_dry_run(Some(output), rows, &sink);
+        println!("   Would fill {} blank cells", filled);
+    } else {
+        print_success(&format!("{} Successfully wrote {} rows to {}", Icon::Success,
+            rows,
+            output.display()));
+        println!("   Filled {} blank cells", filled);
+    }
+
+    Ok(())
+}
+
+/// With no `output`, renames the headers of `input` in place.
+fn rename_headers(ctx: &Ctx, input: &Path, output: Option<&Path>, map: &str, write: &WriteOptions) -> Result<(), Box<dyn Error>> {
+    let mut reader = csv_reader(ctx, input)?;
+    let headers = reader.headers()?.clone();
+
+    let mut renamed: Vec<String> = headers.iter().map(str::to_string).collect();
+    for pair in map.split(',') {
+        let (old, new) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid rename '{}' (expected old=new)", pair.trim()))?;
+        // Resolve against the original headers so renames can't chain
+        let idx = resolve_column(&headers, old, ctx.columns())?;
+        renamed[idx] = new.trim().to_string();
+    }
+
+    let mut writer = match output {
+        Some(output) => create_writer(ctx, Some(output), write, true)?,
+        None => create_in_place_writer(ctx, input, write, true)?,
+    };
+    let output = output.unwrap_or(input);
+    write_header(&mut writer, &renamed)?;
+
+    let mut count = 0;
+    for result in reader.records() {
+        writer.write_record(&result?)?;
+        count += 1;
+    }
+
+    let mut sink = writer.into_inner().map_err(|e| e.into_error())?;
+    sink.commit()?;
+
+    if write.dry_run {
+        report_dry_run(Some(output), count, &sink);
+        return Ok(());
+    }
+
+    print_success(&format!("{} Successfully renamed headers in {}", Icon::Success,
+        output.display()));
+    println!("   Processed {} rows", count);
+
+    Ok(())
+}
+
+/// Writes `indices` of the rows in `range` to `output`, returning the row
+/// count, or `None` under `--dry-run` once the dry run has been reported.
+fn write_columns<R: Read>(
+    ctx: &Ctx,
+    reader: &mut csv::Reader<R>,
+    headers: &StringRecord,
+    indices: &[usize],
+    range: RowRange,
+    output: &Path,
+    write: &WriteOptions,
+) -> Result<Option<usize>, Box<dyn Error>> {
+    let mut writer = create_writer(ctx, Some(output), write, false)?;
+    write_header(&mut writer, &project(headers, indices))?;
+
+    let mut count = 0;
+    for (row, result) in (1..).zip(extract(reader, indices)) {
+        if range.is_past(row) {
+            break;
+        }
+        let record = result?;
+        if range.contains(row) {
+            writer.write_record(&record)?;
+            count += 1;
+        }
+    }
+
+    writer.flush()?;
+
+    if write.dry_run {
+        report_dry_run(Some(output), count, writer.get_ref());
+        return Ok(None);
+    }
+    Ok(Some(count))
+}
+
+fn drop_columns(ctx: &Ctx, input: &Path, output: &Path, columns: &str, write: &WriteOptions) -> Result<(), Box<dyn Error>> {
+    let mut reader = csv_reader(ctx, input)?;
+    let headers = reader.headers()?.clone();
+
+    let dropped = resolve_columns(&headers, columns, ctx.columns())?;
+    let kept: Vec<usize> = (0..headers.len()).filter(|idx| !dropped.contains(idx)).collect();
+
+    let Some(count) = write_columns(ctx, &mut reader, &headers, &kept, RowRange::default(), output, write)? else {
+        return Ok(());
+    };
+
+    print_success(&format!("{} Successfully dropped {} columns, writing {} to {}", Icon::Success,
+        headers.len() - kept.len(),
+        kept.len(),
+        output.display()));
+    println!("   Processed {} rows", count);
+
+    Ok(())
+}
+
+fn concat_csv(ctx: &Ctx, files: &[PathBuf], output: &Path, ignore_header_mismatch: bool, write: &WriteOptions) -> Result<(), Box<dyn Error>> {
+    let mut writer = create_writer(ctx, Some(output), write, true)?;
+
+    let mut first_headers: Option<StringRecord> = None;
+    let mut total = 0;
+
+    for path in files {
+        let mut reader = csv_reader(ctx, path)?;
+
+        let headers = reader.headers()?.clone();
+        match &first_headers {
+            None => {
+                // Header row comes from the first file only
+                write_header(&mut writer, &headers)?;
+                first_headers = Some(headers);
             }
-            
-            // Count unique values for each column
-            if let Some(column_map) = column_stats.get_mut(i) {
-                *column_map.entry(field.to_string()).or_insert(0) += 1;
+            Some(expected) if *expected != headers && !ignore_header_mismatch => {
+                return Err(format!("Headers in {} don't match {} (use --ignore-header-mismatch to append anyway)",
+                    path.display(), files[0].display()).into());
             }
+            Some(_) => {}
+        }
+
+        // Stream rows straight through to the output
+        let mut count = 0;
+        for result in reader.records() {
+            writer.write_record(&result?)?;
+            count += 1;
         }
+
+        println!("   {}: {} rows", path.display(), count);
+        total += count;
     }
-    
-    // Print the statistics
-    println!("📊 CSV File Statistics: {}", file.display());
-    println!("---------------------------------------------------");
-    println!("📏 Dimensions: {} rows × {} columns", row_count, column_count);
-    println!("🔤 Headers: {}", headers.iter().collect::<Vec<_>>().join(", "));
-    println!("📉 Empty cells: {} ({:.2}%)", 
-        empty_cells, 
-        (empty_cells as f64 / (row_count * column_count) as f64) * 100.0
-    );
-    println!();
-    
-    // Print column-specific stats
-    println!("📋 Column Statistics:");
-    for (i, col_name) in headers.iter().enumerate() {
-        if let Some(col_stats) = column_stats.get(i) {
-            let unique_values = col_stats.len();
-            let most_common = col_stats
-                .iter()
-                .max_by_key(|&(_, count)| count)
-                .map(|(val, count)| (val, *count));
-                
-            println!("  {} [{}]:", i + 1, col_name);
-            println!("    - Unique values: {}", unique_values);
-            if let Some((val, count)) = most_common {
-                println!("    - Most common: \"{}\" ({} times, {:.1}%)", 
-                    val,
-                    count,
-                    (count as f64 / row_count as f64) * 100.0
-                );
+
+    writer.flush()?;
+
+    if write.dry_run {
+        report_dry_run(Some(output), total, writer.get_ref());
+        return Ok(());
+    }
+
+    print_success(&format!("{} Successfully concatenated {} files to {}", Icon::Success, 
+        files.len(), 
+        output.display()));
+    println!("   Processed {} rows", total);
+
+    Ok(())
+}
+
+fn group_by(
+    ctx: &Ctx,
+    input: &Path,
+    by: &str,
+    (op, agg): (Aggregate, Option<&str>),
+    output: &Path,
+    counts: &CountOptions,
+    write: &WriteOptions,
+) -> Result<(), Box<dyn Error>> {
+    if counts.percent && op != Aggregate::Count {
+        return Err("--percent only applies to the count operation".into());
+    }
+    let mut reader = csv_reader(ctx, input)?;
+
+    let headers = reader.headers()?.clone();
+    let by_index = resolve_column(&headers, by, ctx.columns())?;
+    let agg_index = match agg {
+        Some(agg) => Some(resolve_column(&headers, agg, ctx.columns())?),
+        None if op == Aggregate::Count => None,
+        None => return Err(format!("--agg is required for the {} operation", op.name()).into()),
+    };
+
+    let mut groups: HashMap<String, Accumulator> = HashMap::new();
+    let mut skipped = 0;
+
+    for result in reader.records() {
+        let record = result?;
+        let key = record.get(by_index).unwrap_or("").to_string();
+        let distinct = groups.len() + usize::from(!groups.contains_key(&key));
+        check_distinct(distinct, counts.max_groups).map_err(|e| max_groups_advice(&headers[by_index], e))?;
+        let acc = groups.entry(key).or_default();
+        acc.count += 1;
+
+        // Non-numeric cells can't feed sum/mean/min/max
+        if let Some(idx) = agg_index.filter(|_| op != Aggregate::Count) {
+            match parse_number(record.get(idx).unwrap_or(""), ctx.number_symbols()) {
+                Some(value) => acc.push(value),
+                None => skipped += 1,
             }
         }
     }
-    
+
+    let mut writer = create_writer(ctx, Some(output), write, false)?;
+
+    let result_header = match agg_index {
+        Some(idx) if op != Aggregate::Count => format!("{}_{}", op.name(), &headers[idx]),
+        _ => op.name().to_string(),
+    };
+    let mut header = vec![&headers[by_index], result_header.as_str()];
+    if counts.percent {
+        header.push("percent");
+    }
+    write_header(&mut writer, header)?;
+
+    // Every row lands in exactly one group
+    let total: usize = groups.values().map(|acc| acc.count).sum();
+    let mut keys: Vec<&String> = groups.keys().collect();
+    keys.sort();
+    for key in keys {
+        let value = groups[key]
+            .result(op)
+            .map(|v| v.to_string())
+            .unwrap_or_default();
+        if counts.percent {
+            writer.write_record([key.as_str(), value.as_str(), &counts.percent_of(groups[key].count, total)])?;
+        } else {
+            writer.write_record([key.as_str(), value.as_str()])?;
+        }
+    }
+
+    writer.flush()?;
+
+    if write.dry_run {
+        report_dry_run(Some(output), groups.len(), writer.get_ref());
+    } else {
+        print_success(&format!("{} Successfully wrote {} groups to {}", Icon::Success, 
+            groups.len(), 
+            output.display()));
+    }
+    if skipped > 0 {
+        println!("   {} Skipped {} non-numeric cells", Icon::Warning, skipped);
+    }
+
     Ok(())
 }
 
-fn find_in_csv(file: &PathBuf, column: &str, term: &str) -> Result<(), Box<dyn Error>> {
-    let file = File::open(file)?;
-    let mut reader = ReaderBuilder::new()
-        .flexible(true)
-        .has_headers(true)
-        .from_reader(file);
-    
+/// Rows and new columns both come out in the order their values first
+/// appear, so dates and the like keep their input order.
+fn pivot(
+    ctx: &Ctx,
+    input: &Path,
+    [index, column, value]: [&str; 3],
+    output: &Path,
+    on_conflict: OnConflict,
+    write: &WriteOptions,
+) -> Result<(), Box<dyn Error>> {
+    let mut reader = csv_reader(ctx, input)?;
+
     let headers = reader.headers()?.clone();
-    let column_index = if let Ok(idx) = column.parse::<usize>() {
-        // If column is a number, use it as index (0-based)
-        if idx >= headers.len() {
-            return Err(format!("Column index {} out of range (0-{})", 
-                idx, headers.len() - 1).into());
+    let index_idx = resolve_column(&headers, index, ctx.columns())?;
+    let column_idx = resolve_column(&headers, column, ctx.columns())?;
+    let value_idx = resolve_column(&headers, value, ctx.columns())?;
+
+    let mut keys: Vec<String> = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+    let mut cells: HashMap<String, HashMap<String, String>> = HashMap::new();
+
+    for (row, result) in reader.records().enumerate() {
+        let record = result?;
+        let key = record.get(index_idx).unwrap_or("");
+        let name = record.get(column_idx).unwrap_or("");
+        let cell = record.get(value_idx).unwrap_or("");
+
+        if !cells.contains_key(key) {
+            keys.push(key.to_string());
         }
-        idx
-    } else {
-        // If column is a name, find its index
-        match headers.iter().position(|h| h == column) {
-            Some(idx) => idx,
-            None => return Err(format!("Column '{}' not found in headers", column).into()),
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
         }
-    };
-    
-    println!("🔍 Searching for \"{}\" in column \"{}\":", term, headers[column_index]);
-    println!("{}", "-".repeat(80));
-    
-    // Print headers
-    print_record(&headers, 0, true)?;
-    println!("{}", "-".repeat(80));
-    
-    let mut matches = 0;
-    
-    for (row_idx, result) in reader.records().enumerate() {
+        let row_cells = cells.entry(key.to_string()).or_default();
+        if row_cells.insert(name.to_string(), cell.to_string()).is_some() && on_conflict == OnConflict::Error {
+            return Err(format!(
+                "Row {}: {} '{}' already has a value for {} '{}'. Pass --on-conflict last to keep the last one",
+                row + 1, &headers[index_idx], key, &headers[column_idx], name
+            )
+            .into());
+        }
+    }
+
+    let mut writer = create_writer(ctx, Some(output), write, false)?;
+    write_header(&mut writer, std::iter::once(&headers[index_idx]).chain(names.iter().map(String::as_str)))?;
+
+    for key in &keys {
+        let row_cells = &cells[key];
+        let values = names.iter().map(|name| row_cells.get(name).map_or("", String::as_str));
+        writer.write_record(std::iter::once(key.as_str()).chain(values))?;
+    }
+
+    writer.flush()?;
+
+    if write.dry_run {
+        report_dry_run(Some(output), keys.len(), writer.get_ref());
+    } else {
+        print_success(&format!("{} Successfully pivoted {} into {} columns in {}", Icon::Success,
+            &headers[column_idx],
+            names.len(),
+            output.display()));
+        println!("   Processed {} rows", keys.len());
+    }
+
+    Ok(())
+}
+
+// Names the column and the way out, so a runaway key set fails with advice
+fn max_groups_advice(column: &str, error: Box<dyn Error>) -> Box<dyn Error> {
+    format!(
+        "{} in column '{}'; it looks more like an ID than a category. \
+         Group by another column, or raise --max-groups if this is expected",
+        error, column
+    )
+    .into()
+}
+
+fn filter_csv(ctx: &Ctx, input: &Path, expr: &str, output: Option<&Path>, write: &WriteOptions) -> Result<(), Box<dyn Error>> {
+    let mut reader = csv_reader(ctx, input)?;
+
+    let headers = reader.headers()?.clone();
+    let filter = Filter::parse(expr, &headers, ctx.columns())?;
+
+    let mut writer = create_writer(ctx, output, write, true)?;
+    write_header(&mut writer, &headers)?;
+
+    let mut total = 0;
+    let mut kept = 0;
+    for result in reader.records() {
         let record = result?;
-        
-        // Check if the term is in the specified column
-        if let Some(field) = record.get(column_index) {
-            if field.to_lowercase().contains(&term.to_lowercase()) {
-                print_record(&record, row_idx + 1, false)?;
-                matches += 1;
-            }
+        total += 1;
+        if filter.matches(&record, ctx.number_symbols()) {
+            writer.write_record(&record)?;
+            kept += 1;
         }
     }
-    
-    println!("{}", "-".repeat(80));
-    println!("Found {} matching rows", matches);
-    
+
+    writer.flush()?;
+
+    if write.dry_run {
+        report_dry_run(output, kept, writer.get_ref());
+    } else if let Some(output) = output {
+        // Stdout carries the CSV itself, so only report when writing a file
+        print_success(&format!("{} Successfully wrote {} of {} rows to {}", Icon::Success,
+            kept,
+            total,
+            output.display()));
+    }
+
     Ok(())
 }
 
-fn extract_columns(input: &PathBuf, output: &PathBuf, columns: &str) -> Result<(), Box<dyn Error>> {
-    // Parse column specifications
-    let column_specs: Vec<&str> = columns.split(',').collect();
-    
-    // Open the input file
-    let input_file = File::open(input)?;
-    let mut reader = ReaderBuilder::new()
-        .flexible(true)
-        .has_headers(true)
-        .from_reader(input_file);
-    
+fn uniq(
+    ctx: &Ctx,
+    input: &Path,
+    column: &str,
+    top: Option<usize>,
+    output: Option<&Path>,
+    counts: &CountOptions,
+    write: &WriteOptions,
+) -> Result<(), Box<dyn Error>> {
+    let mut reader = csv_reader(ctx, input)?;
+
     let headers = reader.headers()?.clone();
-    
-    // Resolve column indices
-    let mut column_indices = Vec::new();
-    for spec in column_specs {
-        let spec = spec.trim();
-        
-        if let Ok(idx) = spec.parse::<usize>() {
-            // If spec is a number, use it as index (0-based)
-            if idx >= headers.len() {
-                return Err(format!("Column index {} out of range (0-{})", 
-                    idx, headers.len() - 1).into());
-            }
-            column_indices.push(idx);
+    let column_index = resolve_column(&headers, column, ctx.columns())?;
+    let name = &headers[column_index];
+    let mut values = count_values(&mut reader, column_index, counts.max_groups).map_err(|e| max_groups_advice(name, e))?;
+    // Shares are of the non-empty cells, taken before --top drops any values.
+    // Empty cells are still counted but get no share
+    let non_empty: usize = values.iter().filter(|(val, _)| !val.is_empty()).map(|(_, count)| count).sum();
+    let share = |val: &str, count: usize| {
+        if val.is_empty() { String::new() } else { counts.percent_of(count, non_empty) }
+    };
+    values.truncate(top.unwrap_or(usize::MAX));
+
+    let Some(output) = output else {
+        let width = values.iter().map(|(val, _)| val.chars().count()).max().unwrap_or(0).max(name.chars().count());
+        let count_width = values.iter().map(|(_, count)| count.to_string().len()).max().unwrap_or(0).max(5);
+        if counts.percent {
+            println!("{:<width$}  {:<count_width$}  percent", name, "count");
         } else {
-            // If spec is a name, find its index
-            match headers.iter().position(|h| h == spec) {
-                Some(idx) => column_indices.push(idx),
-                None => return Err(format!("Column '{}' not found in headers", spec).into()),
+            println!("{:<width$}  count", name);
+        }
+        print_separator();
+        for (val, count) in &values {
+            if counts.percent {
+                println!("{:<width$}  {:<count_width$}  {}", val, count, share(val, *count));
+            } else {
+                println!("{:<width$}  {}", val, count);
             }
         }
+        return Ok(());
+    };
+
+    let mut writer = create_writer(ctx, Some(output), write, false)?;
+    let mut header = vec![name, "count"];
+    if counts.percent {
+        header.push("percent");
     }
-    
-    // Create output file and writer
-    let output_file = File::create(output)?;
-    let mut writer = WriterBuilder::new().from_writer(output_file);
-    
-    // Write header row
-    let mut header_record = StringRecord::new();
-    for &idx in &column_indices {
-        header_record.push_field(&headers[idx]);
+    write_header(&mut writer, header)?;
+    for (val, count) in &values {
+        let mut row = vec![val.clone(), count.to_string()];
+        if counts.percent {
+            row.push(share(val, *count));
+        }
+        writer.write_record(&row)?;
     }
-    writer.write_record(&header_record)?;
-    
-    // Write data rows
+    writer.flush()?;
+
+    if write.dry_run {
+        report_dry_run(Some(output), values.len(), writer.get_ref());
+    } else {
+        print_success(&format!("{} Successfully wrote {} values to {}", Icon::Success,
+            values.len(),
+            output.display()));
+    }
+
+    Ok(())
+}
+
+/// Describes the file as a JSON array of row objects, the shape
+/// `to-json --typed` writes, with each property typed from the data. A column
+/// with empty cells also allows null, and one with only empty cells is just
+/// null.
+fn write_schema(ctx: &Ctx, input: &Path, output: Option<&Path>, required: bool, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let mut reader = csv_reader(ctx, input)?;
+    let columns = infer_schema(&mut reader, ctx.number_symbols())?;
+
+    let mut properties = Map::new();
+    for column in &columns {
+        let kind = match (column.kind, column.nullable) {
+            (None, _) => json!("null"),
+            (Some(kind), false) => json!(kind.name()),
+            (Some(kind), true) => json!([kind.name(), "null"]),
+        };
+        if properties.insert(column.name.clone(), json!({ "type": kind })).is_some() {
+            return Err(format!("Column '{}' appears more than once; schema properties need unique names", column.name).into());
+        }
+    }
+    let mut items = json!({ "type": "object", "properties": properties });
+    if required {
+        let never_empty: Vec<&str> = columns.iter().filter(|c| !c.nullable).map(|c| c.name.as_str()).collect();
+        items["required"] = json!(never_empty);
+    }
+    let title = input.file_name().map_or_else(|| input.display().to_string(), |name| name.to_string_lossy().into_owned());
+    let schema = json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": title,
+        "type": "array",
+        "items": items,
+    });
+
+    let mut out = text_output(output, dry_run)?;
+    serde_json::to_writer_pretty(&mut out, &schema)?;
+    writeln!(out)?;
+    out.flush()?;
+
+    if dry_run {
+        println!("{} Dry run: would write a schema for {} columns ({} bytes) to {}", Icon::DryRun,
+            columns.len(),
+            out.bytes,
+            output.map_or("stdout".into(), |output| output.display().to_string()));
+    } else if let Some(output) = output {
+        print_success(&format!("{} Successfully wrote a schema for {} columns to {}", Icon::Success,
+            columns.len(),
+            output.display()));
+    }
+
+    Ok(())
+}
+
+fn format_rows(ctx: &Ctx, input: &Path, template: &str, output: Option<&Path>, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let mut reader = csv_reader(ctx, input)?;
+    let template = Template::parse(template, reader.headers()?, ctx.columns())?;
+
+    let mut out = text_output(output, dry_run)?;
     let mut count = 0;
     for result in reader.records() {
-        let record = result?;
-        let mut new_record = StringRecord::new();
-        
-        for &idx in &column_indices {
-            if let Some(field) = record.get(idx) {
-                new_record.push_field(field);
-            } else {
-                new_record.push_field("");
-            }
-        }
-        
-        writer.write_record(&new_record)?;
+        writeln!(out, "{}", template.render(&result?))?;
         count += 1;
     }
-    
-    writer.flush()?;
-    
-    println!("✅ Successfully extracted {} columns to {}", 
-        column_indices.len(), 
-        output.display());
-    println!("   Processed {} rows", count);
-    
+    out.flush()?;
+
+    if dry_run {
+        report_dry_run(output, count, &out);
+    } else if let Some(output) = output {
+        print_success(&format!("{} Successfully wrote {} formatted rows to {}", Icon::Success,
+            count,
+            output.display()));
+    }
+
+    Ok(())
+}
+
+fn to_json(ctx: &Ctx, input: &Path, output: Option<&Path>, pretty: bool, ndjson: bool, typed: bool, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    // Column types are only known after a first pass over the whole file
+    let kinds: Option<Vec<Option<ColumnType>>> = if typed {
+        if is_stream(input)? {
+            return Err(format!("--typed reads the input twice, so it needs a regular file, and {} isn't one", input.display()).into());
+        }
+        let columns = infer_schema(&mut csv_reader(ctx, input)?, ctx.number_symbols())?;
+        Some(columns.iter().map(|column| column.kind).collect())
+    } else {
+        None
+    };
+    let mut reader = csv_reader(ctx, input)?;
+    let headers = reader.headers()?.clone();
+
+    let mut out = text_output(output, dry_run)?;
+
+    let to_object = |record: &StringRecord| -> Map<String, Value> {
+        headers
+            .iter()
+            .zip(record.iter())
+            .enumerate()
+            .map(|(idx, (header, field))| {
+                let value = match &kinds {
+                    Some(kinds) => typed_value(field, kinds[idx], ctx.number_symbols()),
+                    None => Value::String(field.to_string()),
+                };
+                (header.to_string(), value)
+            })
+            .collect()
+    };
+
+    let mut count = 0;
+    if ndjson {
+        // Each object goes out as soon as its row is read
+        for result in reader.records() {
+            serde_json::to_writer(&mut out, &to_object(&result?))?;
+            writeln!(out)?;
+            count += 1;
+        }
+    } else {
+        let rows = reader
+            .records()
+            .map(|result| result.map(|record| to_object(&record)))
+            .collect::<Result<Vec<_>, _>>()?;
+        count = rows.len();
+        if pretty {
+            serde_json::to_writer_pretty(&mut out, &rows)?;
+        } else {
+            serde_json::to_writer(&mut out, &rows)?;
+        }
+        writeln!(out)?;
+    }
+    out.flush()?;
+
+    if dry_run {
+        report_dry_run(output, count, &out);
+    } else if let Some(output) = output {
+        print_success(&format!("{} Successfully wrote {} rows as JSON to {}", Icon::Success,
+            count,
+            output.display()));
+    }
+
+    Ok(())
+}
+
+/// A cell as a JSON value of its column's type, matching what `schema`
+/// describes: empty cells are null, and integers that fit exactly are
+/// written without a fraction.
+fn typed_value(cell: &str, kind: Option<ColumnType>, symbols: Option<&[char]>) -> Value {
+    if cell.trim().is_empty() {
+        return Value::Null;
+    }
+    match kind {
+        Some(ColumnType::Boolean) => Value::Bool(cell.trim().eq_ignore_ascii_case("true")),
+        Some(kind @ (ColumnType::Integer | ColumnType::Number)) => match parse_number(cell, symbols) {
+            Some(value) if kind == ColumnType::Integer && value.abs() < 2f64.powi(53) => json!(value as i64),
+            Some(value) => json!(value),
+            None => Value::String(cell.to_string()),
+        },
+        _ => Value::String(cell.to_string()),
+    }
+}
+
+fn validate_csv(ctx: &Ctx, file: &Path, required: Option<&str>, json: bool) -> Result<bool, Box<dyn Error>> {
+    let delimiter = input_delimiter(ctx, file)?;
+    record_input(ctx, file, delimiter);
+    let mut reader = reader_builder(ctx, delimiter)
+        // Always flexible: ragged rows are what validate reports
+        .flexible(true)
+        .from_reader(limit_fields(ctx, open_input(ctx, file)?, delimiter));
+
+    let headers = reader.headers()?.clone();
+    let required = match required {
+        Some(columns) => resolve_columns(&headers, columns, ctx.columns())?,
+        None => Vec::new(),
+    };
+    let validation = validate(&mut reader, &required)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&validation)?);
+        return Ok(validation.is_clean());
+    }
+
+    for problem in &validation.problems {
+        println!("Row {}: {} ({})", problem.row, problem.detail, problem.code);
+    }
+    if validation.is_clean() {
+        print_success(&format!("{} {} rows checked, no problems found", Icon::Success, validation.rows_checked));
+    } else {
+        println!("{} {} problems in {} rows checked",
+            Icon::Warning,
+            validation.problems.len(),
+            validation.rows_checked);
+    }
+
+    Ok(validation.is_clean())
+}
+
+fn diff_csv(ctx: &Ctx, old: &Path, new: &Path, key: &str, json: bool) -> Result<(), Box<dyn Error>> {
+    let mut old_reader = csv_reader(ctx, old)?;
+    let mut new_reader = csv_reader(ctx, new)?;
+    let diff = diff(&mut old_reader, &mut new_reader, key, ctx.columns())?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+        return Ok(());
+    }
+
+    println!("{} Comparing {} with {} by \"{}\"", Icon::Search, old.display(), new.display(), key);
+    if diff.is_empty() {
+        print_success(&format!("{} No differences", Icon::Success));
+        return Ok(());
+    }
+
+    for column in &diff.added_columns {
+        println!("   Column added: {}", column);
+    }
+    for column in &diff.removed_columns {
+        println!("   Column removed: {}", column);
+    }
+    // Colors only reach a terminal; piped output stays plain to be parsed
+    for key in &diff.added {
+        println!("{}", format!("+ {}", key).if_supports_color(Stream::Stdout, |s| s.green()));
+    }
+    for key in &diff.removed {
+        println!("{}", format!("- {}", key).if_supports_color(Stream::Stdout, |s| s.red()));
+    }
+    let colored = stdout_colored();
+    for row in &diff.changed {
+        println!("{}", format!("~ {}", row.key).if_supports_color(Stream::Stdout, |s| s.yellow()));
+        for cell in &row.cells {
+            let old = format!("\"{}\"", cell.old);
+            let new = format!("\"{}\"", cell.new);
+            println!(
+                "    {}: {} -> {}",
+                cell.column.if_supports_color(Stream::Stdout, |s| s.bold()),
+                old.if_supports_color(Stream::Stdout, |s| s.red()),
+                new.if_supports_color(Stream::Stdout, |s| s.green().underline().to_string()),
+            );
+        }
+        // The rest of the row as context, dimmed so the changes stand out
+        if colored && !row.unchanged.is_empty() {
+            let same: Vec<String> = row.unchanged.iter().map(|(column, value)| format!("{}: \"{}\"", column, value)).collect();
+            println!("    {}", same.join(", ").dimmed());
+        }
+    }
+
+    print_separator();
+    println!("{} added, {} removed, {} changed", diff.added.len(), diff.removed.len(), diff.changed.len());
+
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(format: &RowFormat, fields: &[&str]) -> Vec<String> {
+        format.cells(&StringRecord::from(fields.to_vec()))
+    }
+
+    #[test]
+    fn field_limit_stops_an_endless_field() {
+        // An unclosed quote followed by a stream that never ends
+        let input = io::Cursor::new(b"a,b\n1,\"".to_vec()).chain(io::repeat(b'x'));
+        let mut reader = ReaderBuilder::new().from_reader(FieldLimit::new(Box::new(input), 1000, b','));
+        let err = reader.records().find_map(Result::err).unwrap();
+        assert!(err.to_string().contains("A field in row 1, column \"b\" is longer than 1000 bytes"), "{}", err);
+    }
+
+    #[test]
+    fn field_limit_counts_quoted_delimiters_as_content() {
+        let input = io::Cursor::new(b"a\n\"1,2,3\"\n".to_vec());
+        let mut reader = ReaderBuilder::new().from_reader(FieldLimit::new(Box::new(input), 7, b','));
+        assert_eq!(reader.records().next().unwrap().unwrap().get(0), Some("1,2,3"));
+        let input = io::Cursor::new(b"a\n\"1,2,3\"\n".to_vec());
+        let mut reader = ReaderBuilder::new().from_reader(FieldLimit::new(Box::new(input), 4, b','));
+        assert!(reader.records().next().unwrap().is_err());
+    }
+
+    #[test]
+    fn mask_hash_keeps_equal_values_equal() {
+        let hash = |value| MaskMode::Hash.apply(value);
+        assert_eq!(hash("alice@example.com"), hash("alice@example.com"));
+        assert_ne!(hash("alice@example.com"), hash("bob@example.com"));
+        assert_ne!(hash("alice@example.com"), hash("Alice@example.com"));
+        // The first 12 hex digits of its SHA-256, the same on every run
+        assert_eq!(hash("alice@example.com"), "ff8d9819fc0e");
+    }
+
+    #[test]
+    fn mask_full_and_partial() {
+        assert_eq!(MaskMode::Full.apply("secret"), "****");
+        assert_eq!(MaskMode::Partial.apply("555-1234"), "5******4");
+        assert_eq!(MaskMode::Partial.apply("José"), "J**é");
+        assert_eq!(MaskMode::Partial.apply("ab"), "**");
+        for mode in [MaskMode::Full, MaskMode::Partial, MaskMode::Hash] {
+            assert_eq!(mode.apply(""), "");
+        }
+    }
+
+    #[test]
+    fn last_rows_keeps_only_the_limit() {
+        let mut last = LastRows::new(3);
+        let capacity = last.rows.capacity();
+        for row in 0..100_000 {
+            last.push(row);
+        }
+        assert_eq!(last.rows, [99_997, 99_998, 99_999]);
+        // Never grown past what it started with
+        assert_eq!(last.rows.capacity(), capacity);
+    }
+
+    #[test]
+    fn last_rows_fewer_than_the_limit() {
+        let mut last = LastRows::new(5);
+        last.push("a");
+        last.push("b");
+        assert_eq!(last.rows, ["a", "b"]);
+        let mut none = LastRows::new(0);
+        none.push("a");
+        assert!(none.rows.is_empty());
+    }
+
+    #[test]
+    fn cells_truncate_to_the_configured_width() {
+        let format = RowFormat { max_width: Some(8), ..RowFormat::default() };
+        assert_eq!(cells(&format, &["short", "exactly8", "much too long"]), ["short", "exactly8", "much ..."]);
+    }
+
+    #[test]
+    fn cells_truncate_multibyte_text_by_chars() {
+        let field = "café紀元2024年のデータ、そして更に長い";
+        let cells = cells(&RowFormat::default(), &[field]);
+        assert_eq!(cells, ["café紀元2024年のデータ、そ..."]);
+        assert_eq!(cells[0].chars().count(), DEFAULT_COL_WIDTH);
+    }
+
+    #[test]
+    fn cells_cap_a_wide_row() {
+        let fields: Vec<String> = (0..200).map(|i| format!("c{}", i)).collect();
+        let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+        let format = RowFormat { max_columns: Some(10), ..RowFormat::default() };
+        let shown = cells(&format, &fields);
+        assert_eq!(shown.len(), 11);
+        assert_eq!(shown[9], "c9");
+        assert_eq!(shown[10], "... (190 more)");
+
+        let all = RowFormat { max_columns: None, ..RowFormat::default() };
+        assert_eq!(cells(&all, &fields).len(), 200);
+        let exact = RowFormat { max_columns: Some(200), ..RowFormat::default() };
+        assert_eq!(cells(&exact, &fields).len(), 200);
+    }
+
+    #[test]
+    fn cells_without_truncation() {
+        let format = RowFormat { max_width: None, ..RowFormat::default() };
+        let long = "x".repeat(100);
+        assert_eq!(cells(&format, &[&long]), vec![long]);
+    }
+}
@@ -0,0 +1,643 @@
+//! End-to-end checks that run the built binary against small fixture files.
+
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+use tempfile::TempDir;
+
+/// A scratch directory for fixtures, which is also where the binary looks
+/// for its config file, so the user's own settings don't leak in.
+struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        Fixture { dir: tempfile::tempdir().unwrap() }
+    }
+
+    fn file(&self, name: &str, contents: &str) -> PathBuf {
+        let path = self.path(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    fn read(&self, name: &str) -> String {
+        fs::read_to_string(self.path(name)).unwrap()
+    }
+
+    /// The binary set up to run `args` in the fixture directory.
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_csv-Tool"));
+        command
+            .args(args)
+            .current_dir(self.dir.path())
+            .env("XDG_CONFIG_HOME", self.dir.path())
+            .env("NO_COLOR", "1")
+            .env_remove("CSV_TOOL_DELIMITER");
+        command
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    /// Runs a command that has to succeed, returning its stdout.
+    fn ok(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(output.status.success(), "{:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    /// Runs a command that has to fail, returning its stderr.
+    fn err(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(!output.status.success(), "{:?} succeeded", args);
+        String::from_utf8(output.stderr).unwrap()
+    }
+}
+
+const RAGGED: &str = "id,name,city\n1,Ann,Rome\n2,Bob,Paris,extra\n3,Cy,Oslo\n";
+
+#[test]
+fn ragged_rows_are_tolerated_by_default() {
+    let fx = Fixture::new();
+    fx.file("ragged.csv", RAGGED);
+    let out = fx.ok(&["read", "-f", "ragged.csv"]);
+    assert!(out.contains("Total rows: 3"));
+}
+
+#[test]
+fn strict_fails_on_a_ragged_row() {
+    let fx = Fixture::new();
+    fx.file("ragged.csv", RAGGED);
+    let err = fx.err(&["--strict", "read", "-f", "ragged.csv"]);
+    assert!(err.contains("record 2 (line: 3"), "{}", err);
+    assert!(err.contains("found record with 4 fields"), "{}", err);
+}
+
+#[test]
+fn pretty_errors_show_the_ragged_line() {
+    let fx = Fixture::new();
+    fx.file("ragged.csv", RAGGED);
+    let err = fx.err(&["--pretty-errors", "always", "--strict", "read", "-f", "ragged.csv"]);
+    assert!(err.contains("record 2 (line: 3"), "{}", err);
+    // The caret sits under the first extra field
+    assert!(err.ends_with(" --> ragged.csv:3\n  |\n3 | 2,Bob,Paris,extra\n  |             ^\n"), "{}", err);
+
+    let err = fx.err(&["--pretty-errors", "never", "--strict", "read", "-f", "ragged.csv"]);
+    assert!(!err.contains("-->"), "{}", err);
+}
+
+#[test]
+fn append_with_a_custom_input_delimiter_keeps_one_header() {
+    let fx = Fixture::new();
+    fx.file("in.csv", "a;b;c\n1;2;3\n");
+    let args = ["--delimiter", ";", "extract", "-f", "in.csv", "-c", "a,b", "-o", "out.csv", "--append"];
+    fx.ok(&args);
+    fx.ok(&args);
+    assert_eq!(fx.read("out.csv"), "a,b\n1,2\n1,2\n");
+}
+
+fn write_config(fx: &Fixture, toml: &str) {
+    fs::create_dir_all(fx.path("rust-workouts")).unwrap();
+    fx.file("rust-workouts/config.toml", toml);
+}
+
+#[test]
+fn delimiter_defaults_to_comma() {
+    let fx = Fixture::new();
+    fx.file("in.csv", "a,b\n1,2\n");
+    assert!(fx.ok(&["read", "-f", "in.csv"]).contains("H | a | b"));
+}
+
+#[test]
+fn config_delimiter_overrides_the_default() {
+    let fx = Fixture::new();
+    write_config(&fx, "[csv_tool]\ndelimiter = \";\"\n");
+    fx.file("in.csv", "a;b\n1;2\n");
+    assert!(fx.ok(&["read", "-f", "in.csv"]).contains("H | a | b"));
+}
+
+#[test]
+fn env_delimiter_overrides_the_config() {
+    let fx = Fixture::new();
+    write_config(&fx, "[csv_tool]\ndelimiter = \";\"\n");
+    fx.file("in.csv", "a|b\n1|2\n");
+    let output = fx.command(&["read", "-f", "in.csv"]).env("CSV_TOOL_DELIMITER", "|").output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("H | a | b"));
+}
+
+#[test]
+fn flag_delimiter_overrides_env_and_config() {
+    let fx = Fixture::new();
+    write_config(&fx, "[csv_tool]\ndelimiter = \";\"\n");
+    fx.file("in.csv", "a\tb\n1\t2\n");
+    let output = fx.command(&["--delimiter", "tab", "read", "-f", "in.csv"]).env("CSV_TOOL_DELIMITER", "|").output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("H | a | b"));
+}
+
+#[test]
+fn unknown_config_keys_are_an_error() {
+    let fx = Fixture::new();
+    write_config(&fx, "[csv_tool]\ndelimeter = \";\"\n");
+    fx.file("in.csv", "a,b\n1,2\n");
+    let err = fx.err(&["read", "-f", "in.csv"]);
+    assert!(err.contains("Invalid [csv_tool] table"), "{}", err);
+}
+
+const MIXED: &str = "id,price,active,name,blank\n1,9.5,true,Ann,\n2,10,false,,\n";
+
+#[test]
+fn schema_types_a_mixed_file() {
+    let fx = Fixture::new();
+    fx.file("mixed.csv", MIXED);
+    let schema: serde_json::Value = serde_json::from_str(&fx.ok(&["schema", "-f", "mixed.csv", "--required"])).unwrap();
+    let items = &schema["items"];
+    assert_eq!(items["properties"]["id"]["type"], "integer");
+    assert_eq!(items["properties"]["price"]["type"], "number");
+    assert_eq!(items["properties"]["active"]["type"], "boolean");
+    assert_eq!(items["properties"]["name"]["type"], serde_json::json!(["string", "null"]));
+    assert_eq!(items["properties"]["blank"]["type"], "null");
+    assert_eq!(items["required"], serde_json::json!(["id", "price", "active"]));
+}
+
+#[test]
+fn typed_json_matches_the_schema_types() {
+    let fx = Fixture::new();
+    fx.file("mixed.csv", MIXED);
+    let rows: serde_json::Value = serde_json::from_str(&fx.ok(&["to-json", "-f", "mixed.csv", "--typed"])).unwrap();
+    assert_eq!(
+        rows,
+        serde_json::json!([
+            { "id": 1, "price": 9.5, "active": true, "name": "Ann", "blank": null },
+            { "id": 2, "price": 10.0, "active": false, "name": null, "blank": null },
+        ])
+    );
+}
+
+#[test]
+fn untyped_json_keeps_strings() {
+    let fx = Fixture::new();
+    fx.file("mixed.csv", MIXED);
+    let rows: serde_json::Value = serde_json::from_str(&fx.ok(&["to-json", "-f", "mixed.csv"])).unwrap();
+    assert_eq!(rows[0]["id"], "1");
+    assert_eq!(rows[1]["name"], "");
+}
+
+#[test]
+fn dry_runs_leave_the_output_alone() {
+    let fx = Fixture::new();
+    fx.file("mixed.csv", MIXED);
+    fx.file("out.json", "keep");
+    let runs: [&[&str]; 3] = [
+        &["to-json", "-f", "mixed.csv", "-o", "out.json", "--dry-run"],
+        &["schema", "-f", "mixed.csv", "-o", "out.json", "--dry-run"],
+        &["format", "-f", "mixed.csv", "-t", "{id}", "-o", "out.json", "--dry-run"],
+    ];
+    for args in runs {
+        let out = fx.ok(args);
+        assert!(out.contains("Dry run: would write"), "{}", out);
+        assert_eq!(fx.read("out.json"), "keep");
+    }
+}
+
+#[test]
+fn dry_run_reports_the_rows_and_bytes() {
+    let fx = Fixture::new();
+    fx.file("mixed.csv", MIXED);
+    let out = fx.ok(&["format", "-f", "mixed.csv", "-t", "{id}", "--dry-run"]);
+    assert!(out.contains("would write 2 rows (4 bytes) to stdout"), "{}", out);
+    assert!(!out.starts_with('1'), "{}", out);
+}
+
+#[test]
+fn max_col_width_needs_room_for_the_ellipsis() {
+    let fx = Fixture::new();
+    fx.file("in.csv", "a\nlonger than four\n");
+    let err = fx.err(&["read", "-f", "in.csv", "--max-col-width", "3"]);
+    assert!(err.contains("3 is not in 4.."), "{}", err);
+    assert!(fx.ok(&["read", "-f", "in.csv", "--max-col-width", "4"]).contains("l..."));
+}
+
+#[test]
+fn stats_json_reports_the_dimensions() {
+    let fx = Fixture::new();
+    fx.file("mixed.csv", MIXED);
+    let stats: serde_json::Value = serde_json::from_str(&fx.ok(&["stats", "-f", "mixed.csv", "--format", "json"])).unwrap();
+    assert_eq!(stats["row_count"], 2);
+    assert_eq!(stats["column_count"], 5);
+    assert_eq!(stats["columns"].as_array().unwrap().len(), 5);
+}
+
+#[test]
+fn read_and_find_truncate_international_text() {
+    let fx = Fixture::new();
+    fx.file("intl.csv", "note\ncafé紀元2024年のデータ、そして更に長い\n");
+    let expected = "café紀元2024年のデータ、そ...";
+    assert!(fx.ok(&["read", "-f", "intl.csv"]).contains(expected));
+    assert!(fx.ok(&["find", "-f", "intl.csv", "-c", "note", "-t", "café"]).contains(expected));
+}
+
+#[test]
+fn head_and_tail_on_a_large_file() {
+    let fx = Fixture::new();
+    let mut data = String::from("n\n");
+    for n in 1..=50_000 {
+        data.push_str(&format!("{}\n", n));
+    }
+    fx.file("big.csv", &data);
+
+    let tail = fx.ok(&["read", "-f", "big.csv", "--tail", "2", "--no-line-numbers"]);
+    let rows: Vec<&str> = tail.lines().filter(|line| line.parse::<u32>().is_ok()).collect();
+    assert_eq!(rows, ["49999", "50000"]);
+    assert!(tail.contains("Total rows: 50000"), "{}", tail);
+
+    let head = fx.ok(&["read", "-f", "big.csv", "--head", "2", "--no-line-numbers"]);
+    let rows: Vec<&str> = head.lines().filter(|line| line.parse::<u32>().is_ok()).collect();
+    assert_eq!(rows, ["1", "2"]);
+}
+
+const PEOPLE: &str = "name,city\nAnn,Rome\nBob,Paris\nCy,Rome\n";
+
+#[test]
+fn ascii_mode_writes_only_ascii() {
+    let fx = Fixture::new();
+    fx.file("people.csv", PEOPLE);
+    let runs: [&[&str]; 5] = [
+        &["stats", "-f", "people.csv"],
+        &["stats", "-f", "people.csv", "--column", "city"],
+        &["find", "-f", "people.csv", "-c", "city", "-t", "rome"],
+        &["extract", "-f", "people.csv", "-c", "name", "-o", "out.csv"],
+        &["read", "-f", "people.csv", "--border"],
+    ];
+    for args in runs {
+        let flagged: Vec<&str> = std::iter::once("--ascii").chain(args.iter().copied()).collect();
+        for output in [fx.run(&flagged), fx.command(args).env("NO_EMOJI", "1").output().unwrap()] {
+            assert!(output.status.success(), "{:?}", args);
+            assert!(output.stdout.is_ascii(), "{:?}: {}", args, String::from_utf8_lossy(&output.stdout));
+            assert!(output.stderr.is_ascii(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        }
+    }
+}
+
+#[test]
+fn emoji_by_default() {
+    let fx = Fixture::new();
+    fx.file("people.csv", PEOPLE);
+    assert!(!fx.ok(&["stats", "-f", "people.csv"]).is_ascii());
+}
+
+#[test]
+fn auto_delimiter_reports_what_it_picked() {
+    let fx = Fixture::new();
+    fx.file("semi.csv", "a;b\n1;2\n");
+    fx.file("odd.csv", "a,b;c\n1,2;3\n");
+
+    let output = fx.run(&["--auto-delimiter", "read", "-f", "semi.csv"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("H | a | b"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Detected delimiter ';' in semi.csv"));
+
+    let output = fx.run(&["--auto-delimiter", "read", "-f", "odd.csv"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Couldn't detect the delimiter of odd.csv; using ','"));
+
+    // `find --quiet` keeps the note off stderr too
+    let output = fx.run(&["--auto-delimiter", "find", "-f", "semi.csv", "-c", "a", "-t", "1", "--quiet"]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn drop_removes_the_middle_column() {
+    let fx = Fixture::new();
+    fx.file("in.csv", "a,b,c\n1,2,3\n4,5,6\n");
+    fx.ok(&["drop", "-f", "in.csv", "-o", "out.csv", "-c", "b"]);
+    assert_eq!(fx.read("out.csv"), "a,c\n1,3\n4,6\n");
+    fx.ok(&["drop", "-f", "in.csv", "-o", "idx.csv", "-c", "2,0"]);
+    assert_eq!(fx.read("idx.csv"), "b\n2\n5\n");
+}
+
+#[test]
+fn drop_rejects_an_unknown_column() {
+    let fx = Fixture::new();
+    fx.file("in.csv", "a,b,c\n1,2,3\n");
+    let err = fx.err(&["drop", "-f", "in.csv", "-o", "out.csv", "-c", "d"]);
+    assert!(err.contains("Column 'd' not found in headers"), "{}", err);
+}
+
+#[test]
+fn windows_1252_round_trip() {
+    let fx = Fixture::new();
+    // "Café" and "naïve" in Windows-1252
+    fs::write(fx.path("legacy.csv"), b"word,n\nCaf\xe9,1\nna\xefve,2\n").unwrap();
+
+    let out = fx.ok(&["--encoding", "windows-1252", "read", "-f", "legacy.csv"]);
+    assert!(out.contains("Café") && out.contains("naïve"), "{}", out);
+
+    fx.ok(&["--encoding", "windows-1252", "extract", "-f", "legacy.csv", "-c", "word", "-o", "utf8.csv"]);
+    assert_eq!(fx.read("utf8.csv"), "word\nCafé\nnaïve\n");
+
+    let args = ["--encoding", "latin1", "--output-encoding", "windows-1252", "extract", "-f", "legacy.csv", "-c", "word", "-o", "back.csv"];
+    fx.ok(&args);
+    assert_eq!(fs::read(fx.path("back.csv")).unwrap(), b"word\nCaf\xe9\nna\xefve\n");
+}
+
+#[test]
+fn undecodable_utf8_is_an_error_by_default() {
+    let fx = Fixture::new();
+    fs::write(fx.path("legacy.csv"), b"word\nCaf\xe9\n").unwrap();
+    fx.err(&["read", "-f", "legacy.csv"]);
+}
+
+#[test]
+fn mask_leaves_other_columns_alone() {
+    let fx = Fixture::new();
+    fx.file("in.csv", "id,email\n1,a@x.com\n2,\n3,a@x.com\n");
+    fx.ok(&["mask", "-f", "in.csv", "-o", "out.csv", "-c", "email", "--mode", "hash"]);
+    let out = fx.read("out.csv");
+    let rows: Vec<Vec<&str>> = out.lines().map(|line| line.split(',').collect()).collect();
+    assert_eq!(rows[0], ["id", "email"]);
+    assert_eq!(rows.iter().skip(1).map(|row| row[0]).collect::<Vec<_>>(), ["1", "2", "3"]);
+    assert_eq!(rows[1][1], rows[3][1]);
+    assert_eq!(rows[2][1], "");
+}
+
+#[test]
+fn wide_rows_are_capped() {
+    let fx = Fixture::new();
+    let header: Vec<String> = (0..200).map(|i| format!("h{}", i)).collect();
+    let row: Vec<String> = (0..200).map(|i| i.to_string()).collect();
+    fx.file("wide.csv", &format!("{}\n{}\n", header.join(","), row.join(",")));
+
+    let out = fx.ok(&["read", "-f", "wide.csv"]);
+    assert!(out.contains("h19 | ... (180 more)"), "{}", out);
+    assert!(!out.contains("h20"));
+    assert!(fx.ok(&["read", "-f", "wide.csv", "--max-columns", "50"]).contains("h49 | ... (150 more)"));
+    let all = fx.ok(&["read", "-f", "wide.csv", "--max-columns", "0"]);
+    assert!(all.contains("| h199") && !all.contains("more)"));
+}
+
+#[test]
+fn uniq_percent_is_of_the_non_empty_cells() {
+    let fx = Fixture::new();
+    fx.file("c.csv", "city\nRome\nParis\n\nRome\nOslo\n");
+    fx.ok(&["uniq", "-f", "c.csv", "-c", "city", "--percent", "--precision", "2", "-o", "out.csv"]);
+    assert_eq!(fx.read("out.csv"), "city,count,percent\nRome,2,50.00\nOslo,1,25.00\nParis,1,25.00\n");
+}
+
+#[test]
+fn group_by_count_percent_sums_to_a_hundred() {
+    let fx = Fixture::new();
+    fx.file("c.csv", "city\nRome\nParis\nRome\n");
+    fx.ok(&["group-by", "-f", "c.csv", "-b", "city", "-o", "out.csv", "--percent"]);
+    let out = fx.read("out.csv");
+    let mut lines = out.lines();
+    assert_eq!(lines.next(), Some("city,count,percent"));
+    let percents: Vec<f64> = lines.map(|line| line.rsplit(',').next().unwrap().parse().unwrap()).collect();
+    assert_eq!(percents.len(), 2);
+    assert!((percents.iter().sum::<f64>() - 100.0).abs() <= 0.1, "{:?}", percents);
+}
+
+const TABLE: &str = "item,qty\nApple,5\nBanana,120\nKiwi,\n";
+
+#[test]
+fn bordered_table_snapshot() {
+    let fx = Fixture::new();
+    fx.file("t.csv", TABLE);
+    assert_eq!(
+        fx.ok(&["read", "-f", "t.csv", "--border"]),
+        "┌───────┬────────┬─────┐\n\
+         │     H │ item   │ qty │\n\
+         ├───────┼────────┼─────┤\n\
+         │     1 │ Apple  │   5 │\n\
+         │     2 │ Banana │ 120 │\n\
+         │     3 │ Kiwi   │     │\n\
+         └───────┴────────┴─────┘\n\
+         Total rows: 3\n"
+    );
+    assert_eq!(
+        fx.ok(&["--ascii", "read", "-f", "t.csv", "--border", "--no-line-numbers"]),
+        "+--------+-----+\n\
+         | item   | qty |\n\
+         +--------+-----+\n\
+         | Apple  |   5 |\n\
+         | Banana | 120 |\n\
+         | Kiwi   |     |\n\
+         +--------+-----+\n\
+         Total rows: 3\n"
+    );
+}
+
+#[test]
+fn aligned_table_right_aligns_numbers() {
+    let fx = Fixture::new();
+    fx.file("t.csv", TABLE);
+    let out = fx.ok(&["read", "-f", "t.csv", "--align", "--separator", "  ", "--no-line-numbers"]);
+    let rows: Vec<&str> = out.lines().filter(|line| !line.starts_with('-')).collect();
+    assert_eq!(rows, ["item    qty", "Apple     5", "Banana  120", "Kiwi", "Total rows: 3"]);
+}
+
+#[test]
+fn trim_fixes_padded_headers_and_values() {
+    let fx = Fixture::new();
+    fx.file("p.csv", " Name , city\n Ann ,Rome\nAnn,Rome \n");
+
+    let err = fx.err(&["uniq", "-f", "p.csv", "-c", "Name", "-o", "raw.csv"]);
+    assert!(err.contains("Column 'Name' not found in headers"), "{}", err);
+    // The padded header still resolves by index, and the padding splits the values
+    fx.ok(&["uniq", "-f", "p.csv", "-c", "0", "-o", "raw.csv"]);
+    assert_eq!(fx.read("raw.csv"), " Name ,count\n Ann ,1\nAnn,1\n");
+
+    fx.ok(&["--trim", "uniq", "-f", "p.csv", "-c", "Name", "-o", "trimmed.csv"]);
+    assert_eq!(fx.read("trimmed.csv"), "Name,count\nAnn,2\n");
+    fx.ok(&["--trim", "extract", "-f", "p.csv", "-c", "city", "-o", "city.csv"]);
+    assert_eq!(fx.read("city.csv"), "city\nRome\nRome\n");
+}
+
+const ACCOUNTS: &str = "name,status,region\nAnn,active,west\nBob,active,east\nCy,closed,west\nDi,closed,east\n";
+
+#[test]
+fn find_with_two_conditions() {
+    let fx = Fixture::new();
+    fx.file("s.csv", ACCOUNTS);
+    let pairs = ["-c", "status", "-t", "active", "-c", "region", "-t", "west"];
+    let find = |extra: &[&str]| {
+        let args: Vec<&str> = ["find", "-f", "s.csv"].iter().chain(&pairs).chain(extra).copied().collect();
+        fx.ok(&args)
+    };
+
+    assert_eq!(find(&["--count"]), "1\n");
+    assert_eq!(find(&["--count", "--match", "all"]), "1\n");
+    assert_eq!(find(&["--count", "--match", "any"]), "3\n");
+    let any = find(&["--match", "any"]);
+    assert!(any.contains("| Ann |") && any.contains("| Bob |") && any.contains("| Cy |"));
+    assert!(!any.contains("| Di |"));
+}
+
+#[test]
+fn find_needs_a_term_per_column() {
+    let fx = Fixture::new();
+    fx.file("s.csv", ACCOUNTS);
+    let err = fx.err(&["find", "-f", "s.csv", "-c", "status", "-t", "active", "-c", "region"]);
+    assert!(err.contains("Got 2 --column and 1 --term values"), "{}", err);
+}
+
+fn utf16(text: &str, little_endian: bool) -> Vec<u8> {
+    let mut bytes = if little_endian { vec![0xFF, 0xFE] } else { vec![0xFE, 0xFF] };
+    for unit in text.encode_utf16() {
+        bytes.extend(if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() });
+    }
+    bytes
+}
+
+#[test]
+fn utf16_files_are_decoded_by_their_bom() {
+    let fx = Fixture::new();
+    fs::write(fx.path("le.csv"), utf16("name,city\nJosé,Kraków\n", true)).unwrap();
+    fs::write(fx.path("be.csv"), utf16("name,city\nJosé,Kraków\n", false)).unwrap();
+    for file in ["le.csv", "be.csv"] {
+        fx.ok(&["extract", "-f", file, "-c", "city", "-o", "out.csv"]);
+        assert_eq!(fx.read("out.csv"), "city\nKraków\n", "{}", file);
+    }
+}
+
+#[test]
+fn a_utf8_bom_is_stripped() {
+    let fx = Fixture::new();
+    fx.file("bom.csv", "\u{feff}name,city\nAnn,Rome\n");
+    fx.ok(&["extract", "-f", "bom.csv", "-c", "name", "-o", "out.csv"]);
+    assert_eq!(fx.read("out.csv"), "name\nAnn\n");
+}
+
+#[test]
+fn max_field_size_stops_a_runaway_field() {
+    let fx = Fixture::new();
+    // An unclosed quote swallows the rest of a 20 MB file into one field
+    let mut data = String::from("a,b\n1,\"");
+    data.push_str(&"x".repeat(20_000_000));
+    data.push_str("\n2,3\n");
+    fx.file("big.csv", &data);
+
+    let err = fx.err(&["--max-field-size", "1000", "read", "-f", "big.csv"]);
+    assert!(err.contains("A field in row 1, column \"b\" is longer than 1000 bytes (--max-field-size)"), "{}", err);
+
+    fx.file("ok.csv", "a,b\n1,12345\n");
+    fx.ok(&["--max-field-size", "5", "read", "-f", "ok.csv"]);
+    fx.err(&["--max-field-size", "4", "read", "-f", "ok.csv"]);
+}
+
+const THREE_ROWS: &str = "n,word\n1,one\n2,two\n3,three\n";
+
+#[test]
+fn reverse_prints_the_last_row_first() {
+    let fx = Fixture::new();
+    fx.file("t.csv", THREE_ROWS);
+    let out = fx.ok(&["read", "-f", "t.csv", "--reverse"]);
+    let rows: Vec<&str> = out.lines().filter(|line| line.contains(" | ") && !line.contains("H |")).collect();
+    assert_eq!(rows, ["    3 | 3 | three", "    2 | 2 | two", "    1 | 1 | one"]);
+
+    // After --head, and with the rows' own numbers
+    let out = fx.ok(&["read", "-f", "t.csv", "--reverse", "--head", "2", "--columns", "word"]);
+    let rows: Vec<&str> = out.lines().filter(|line| line.contains(" | ") && !line.contains("H |")).collect();
+    assert_eq!(rows, ["    2 | two", "    1 | one"]);
+}
+
+#[test]
+fn no_line_numbers_drops_the_indicator_column() {
+    let fx = Fixture::new();
+    fx.file("t.csv", THREE_ROWS);
+    let out = fx.ok(&["read", "-f", "t.csv", "--no-line-numbers", "--reverse"]);
+    let rows: Vec<&str> = out.lines().filter(|line| line.contains(" | ")).collect();
+    assert_eq!(rows, ["n | word", "3 | three", "2 | two", "1 | one"]);
+}
+
+const OLD: &str = "id,name,city\n1,Ann,Rome\n2,Bob,Oslo\n3,Cy,Lima\n";
+const NEW: &str = "id,name,city\n1,Ann,Paris\n3,Cy,Lima\n4,Di,Kyiv\n";
+
+#[test]
+fn plain_diff_snapshot() {
+    let fx = Fixture::new();
+    fx.file("old.csv", OLD);
+    fx.file("new.csv", NEW);
+    assert_eq!(
+        fx.ok(&["--ascii", "diff", "--old", "old.csv", "--new", "new.csv", "-k", "id"]),
+        format!(
+            "[find] Comparing old.csv with new.csv by \"id\"\n\
+             + 4\n\
+             - 2\n\
+             ~ 1\n    \
+             city: \"Rome\" -> \"Paris\"\n\
+             {}\n\
+             1 added, 1 removed, 1 changed\n",
+            "-".repeat(80)
+        )
+    );
+}
+
+#[test]
+fn colored_diff_snapshot() {
+    let fx = Fixture::new();
+    fx.file("old.csv", OLD);
+    fx.file("new.csv", NEW);
+    let output = fx
+        .command(&["--ascii", "diff", "--old", "old.csv", "--new", "new.csv", "-k", "id"])
+        .env_remove("NO_COLOR")
+        .env("FORCE_COLOR", "1")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "[find] Comparing old.csv with new.csv by \"id\"\n\
+             \x1b[32m+ 4\x1b[39m\n\
+             \x1b[31m- 2\x1b[39m\n\
+             \x1b[33m~ 1\x1b[39m\n    \
+             \x1b[1mcity\x1b[0m: \x1b[31m\"Rome\"\x1b[39m -> \x1b[4m\x1b[32m\"Paris\"\x1b[39m\x1b[0m\n    \
+             \x1b[2mid: \"1\", name: \"Ann\"\x1b[0m\n\
+             \x1b[2m{}\x1b[0m\n\
+             1 added, 1 removed, 1 changed\n",
+            "-".repeat(80)
+        )
+    );
+}
+
+#[test]
+fn ignore_case_headers_warns_about_a_collision() {
+    let fx = Fixture::new();
+    fx.file("people.csv", "Name,name\nAnn,x\nBob,y\n");
+    let output = fx.run(&["--ignore-case-headers", "find", "--file", "people.csv", "--column", "NAME", "--term", "ann"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("matches 'Name' (0), 'name' (1) ignoring case"), "{}", stderr);
+    assert!(String::from_utf8(output.stdout).unwrap().contains("Ann"));
+}
+
+#[test]
+fn number_symbols_replace_the_lenient_defaults() {
+    let fx = Fixture::new();
+    fx.file("scores.csv", "name,score\nAnn,12pts\nBob,8pts\nCy,$20\n");
+    let args = ["--lenient-numbers", "--number-symbols", "pts", "filter", "--file", "scores.csv", "--where", "score > 10"];
+    let out = fx.ok(&args);
+    assert!(out.contains("Ann"), "{}", out);
+    assert!(!out.contains("Bob"), "{}", out);
+    // "$20" no longer parses, so it's compared as text: "$" sorts before "1"
+    assert!(!out.contains("Cy"), "{}", out);
+
+    let err = fx.err(&["--number-symbols", "pts", "filter", "--file", "scores.csv", "--where", "score > 10"]);
+    assert!(err.contains("--lenient-numbers"), "{}", err);
+}
+
+#[test]
+fn config_number_symbols_apply_under_lenient_numbers() {
+    let fx = Fixture::new();
+    write_config(&fx, "[csv_tool]\nnumber_symbols = \"kg\"\n");
+    fx.file("weights.csv", "item,weight\nbox,12kg\nbag,3kg\n");
+    let out = fx.ok(&["--lenient-numbers", "filter", "--file", "weights.csv", "--where", "weight > 5"]);
+    assert!(out.contains("box") && !out.contains("bag"), "{}", out);
+}
@@ -0,0 +1,137 @@
+//! Row-level comparison of two CSV files that share a key column.
+
+use crate::{ColumnOptions, resolve_column};
+use csv::{Reader, StringRecord};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    io::Read,
+};
+
+/// One cell that differs between the old and new version of a row.
+#[derive(Serialize)]
+pub struct CellChange {
+    pub column: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// A key present in both files whose rows differ.
+#[derive(Serialize)]
+pub struct RowChange {
+    pub key: String,
+    pub cells: Vec<CellChange>,
+    /// The shared columns whose cell is the same in both, as (column, value)
+    /// in the old file's column order. Only for display, so not serialized.
+    #[serde(skip)]
+    pub unchanged: Vec<(String, String)>,
+}
+
+/// Keys are listed in the order they appear: added and changed rows in the
+/// new file's order, removed rows in the old file's.
+#[derive(Serialize)]
+pub struct Diff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<RowChange>,
+    /// Columns only in the new file, and only in the old one. Their cells
+    /// aren't compared.
+    pub added_columns: Vec<String>,
+    pub removed_columns: Vec<String>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.added_columns.is_empty()
+            && self.removed_columns.is_empty()
+    }
+}
+
+/// Compares `old` and `new` row by row, matching rows on the `key` column
+/// (a name or index, resolved in each file). Cells are compared by header
+/// name, so reordered columns don't count as changes. A key occurring twice
+/// in one file is an error, since its rows can't be paired up.
+pub fn diff<R: Read, S: Read>(
+    old: &mut Reader<R>,
+    new: &mut Reader<S>,
+    key: &str,
+    columns: ColumnOptions,
+) -> Result<Diff, Box<dyn Error>> {
+    let old_headers = old.headers()?.clone();
+    let new_headers = new.headers()?.clone();
+    let old_key = resolve_column(&old_headers, key, columns)?;
+    let new_key = resolve_column(&new_headers, key, columns)?;
+
+    // (column name, old index, new index) for every column in both files
+    let shared: Vec<(&str, usize, usize)> = old_headers
+        .iter()
+        .enumerate()
+        .filter_map(|(old_idx, name)| {
+            let new_idx = new_headers.iter().position(|h| h == name)?;
+            Some((name, old_idx, new_idx))
+        })
+        .collect();
+
+    let mut old_order = Vec::new();
+    let mut old_rows: HashMap<String, StringRecord> = HashMap::new();
+    for result in old.records() {
+        let record = result?;
+        let row_key = record.get(old_key).unwrap_or("").to_string();
+        if old_rows.contains_key(&row_key) {
+            return Err(format!("Key '{}' appears more than once in the old file", row_key).into());
+        }
+        old_order.push(row_key.clone());
+        old_rows.insert(row_key, record);
+    }
+
+    let mut diff = Diff {
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+        added_columns: new_headers
+            .iter()
+            .filter(|name| !old_headers.iter().any(|h| h == *name))
+            .map(str::to_string)
+            .collect(),
+        removed_columns: old_headers
+            .iter()
+            .filter(|name| !new_headers.iter().any(|h| h == *name))
+            .map(str::to_string)
+            .collect(),
+    };
+
+    let mut seen: HashSet<String> = HashSet::new();
+    for result in new.records() {
+        let record = result?;
+        let row_key = record.get(new_key).unwrap_or("").to_string();
+        if !seen.insert(row_key.clone()) {
+            return Err(format!("Key '{}' appears more than once in the new file", row_key).into());
+        }
+
+        let Some(old_record) = old_rows.get(&row_key) else {
+            diff.added.push(row_key);
+            continue;
+        };
+        let mut cells = Vec::new();
+        let mut unchanged = Vec::new();
+        for &(name, old_idx, new_idx) in &shared {
+            let before = old_record.get(old_idx).unwrap_or("");
+            let after = record.get(new_idx).unwrap_or("");
+            if before == after {
+                unchanged.push((name.to_string(), before.to_string()));
+            } else {
+                cells.push(CellChange { column: name.to_string(), old: before.to_string(), new: after.to_string() });
+            }
+        }
+        if !cells.is_empty() {
+            diff.changed.push(RowChange { key: row_key, cells, unchanged });
+        }
+    }
+
+    diff.removed = old_order.into_iter().filter(|key| !seen.contains(key)).collect();
+    Ok(diff)
+}
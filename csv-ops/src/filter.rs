@@ -0,0 +1,316 @@
+//! A small boolean expression language for selecting rows, e.g.
+//! `age > 30 and (status == active or status == pending)`.
+//!
+//! Grammar, loosest binding first:
+//!
+//! ```text
+//! expr       := and_expr ("or" and_expr)*
+//! and_expr   := term ("and" term)*
+//! term       := "(" expr ")" | comparison
+//! comparison := column op value
+//! op         := "==" | "!=" | "<" | "<=" | ">" | ">=" | "contains"
+//! ```
+//!
+//! Columns are header names or 0-based indices; columns and values may be
+//! quoted with `'` or `"` to include spaces or operator characters.
+
+use crate::{ColumnOptions, parse_number, resolve_column};
+use csv::StringRecord;
+use std::{cmp::Ordering, error::Error, fmt};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Debug)]
+pub enum Filter {
+    Compare { column: usize, op: Op, value: String },
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+impl Filter {
+    /// Parses `expr`, resolving its column references against `headers`.
+    pub fn parse(expr: &str, headers: &StringRecord, columns: ColumnOptions) -> Result<Filter, Box<dyn Error>> {
+        let mut parser = Parser { tokens: tokenize(expr)?, pos: 0, headers, columns };
+        let filter = parser.expr()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(filter),
+            Some(token) => Err(format!("Unexpected {} in filter expression", token).into()),
+        }
+    }
+
+    /// Whether `record` satisfies the filter. Missing cells compare as "";
+    /// `symbols` is passed on to [`parse_number`].
+    pub fn matches(&self, record: &StringRecord, symbols: Option<&[char]>) -> bool {
+        match self {
+            Filter::Compare { column, op, value } => compare(record.get(*column).unwrap_or(""), *op, value, symbols),
+            Filter::And(a, b) => a.matches(record, symbols) && b.matches(record, symbols),
+            Filter::Or(a, b) => a.matches(record, symbols) || b.matches(record, symbols),
+        }
+    }
+}
+
+// Compares numerically when both sides parse as numbers, as text otherwise.
+fn compare(cell: &str, op: Op, value: &str, symbols: Option<&[char]>) -> bool {
+    let cell = cell.trim();
+    if op == Op::Contains {
+        return cell.to_lowercase().contains(&value.to_lowercase());
+    }
+
+    let ordering = match (parse_number(cell, symbols), parse_number(value, symbols)) {
+        (Some(a), Some(b)) => match a.partial_cmp(&b) {
+            Some(ordering) => ordering,
+            None => return op == Op::Ne,
+        },
+        _ => cell.cmp(value),
+    };
+    match op {
+        Op::Eq => ordering == Ordering::Equal,
+        Op::Ne => ordering != Ordering::Equal,
+        Op::Lt => ordering == Ordering::Less,
+        Op::Le => ordering != Ordering::Greater,
+        Op::Gt => ordering == Ordering::Greater,
+        Op::Ge => ordering != Ordering::Less,
+        Op::Contains => unreachable!(),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(Op),
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "'{}'", word),
+            Token::Quoted(text) => write!(f, "\"{}\"", text),
+            Token::Op(op) => write!(f, "operator {:?}", op),
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+        }
+    }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            }
+            '\'' | '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(next) if next == c => break,
+                        Some(next) => text.push(next),
+                        None => return Err("Unterminated quote in filter expression".into()),
+                    }
+                }
+                tokens.push(Token::Quoted(text));
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let followed_by_eq = chars.next_if_eq(&'=').is_some();
+                let op = match (c, followed_by_eq) {
+                    ('=', true) => Op::Eq,
+                    ('!', true) => Op::Ne,
+                    ('<', true) => Op::Le,
+                    ('<', false) => Op::Lt,
+                    ('>', true) => Op::Ge,
+                    ('>', false) => Op::Gt,
+                    _ => return Err(format!("Unknown operator '{}' in filter expression", c).into()),
+                };
+                tokens.push(Token::Op(op));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(next) = chars.next_if(|&n| !n.is_whitespace() && !"()'\"=!<>".contains(n)) {
+                    word.push(next);
+                }
+                if word.eq_ignore_ascii_case("contains") {
+                    tokens.push(Token::Op(Op::Contains));
+                } else {
+                    tokens.push(Token::Word(word));
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    headers: &'a StringRecord,
+    columns: ColumnOptions,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expr(&mut self) -> Result<Filter, Box<dyn Error>> {
+        let mut filter = self.and_expr()?;
+        while self.eat_keyword("or") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.and_expr()?));
+        }
+        Ok(filter)
+    }
+
+    fn and_expr(&mut self) -> Result<Filter, Box<dyn Error>> {
+        let mut filter = self.term()?;
+        while self.eat_keyword("and") {
+            filter = Filter::And(Box::new(filter), Box::new(self.term()?));
+        }
+        Ok(filter)
+    }
+
+    fn term(&mut self) -> Result<Filter, Box<dyn Error>> {
+        if self.tokens.get(self.pos) == Some(&Token::Open) {
+            self.pos += 1;
+            let filter = self.expr()?;
+            return match self.next() {
+                Some(Token::Close) => Ok(filter),
+                _ => Err("Missing ')' in filter expression".into()),
+            };
+        }
+
+        let column = match self.next() {
+            Some(Token::Word(name)) | Some(Token::Quoted(name)) => name.clone(),
+            Some(token) => return Err(format!("Expected a column name, found {}", token).into()),
+            None => return Err("Expected a column name at end of filter expression".into()),
+        };
+        let op = match self.next() {
+            Some(Token::Op(op)) => *op,
+            Some(token) => return Err(format!("Expected an operator after '{}', found {}", column, token).into()),
+            None => return Err(format!("Expected an operator after '{}'", column).into()),
+        };
+        let value = match self.next() {
+            Some(Token::Word(value)) | Some(Token::Quoted(value)) => value.clone(),
+            Some(token) => return Err(format!("Expected a value after operator, found {}", token).into()),
+            None => return Err("Expected a value at end of filter expression".into()),
+        };
+
+        Ok(Filter::Compare { column: resolve_column(self.headers, &column, self.columns)?, op, value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> StringRecord {
+        StringRecord::from(vec!["name", "age", "city"])
+    }
+
+    fn parse(expr: &str) -> Result<Filter, String> {
+        Filter::parse(expr, &headers(), ColumnOptions::default()).map_err(|e| e.to_string())
+    }
+
+    /// The parsed tree with every operation bracketed, to check grouping.
+    fn shape(filter: &Filter) -> String {
+        match filter {
+            Filter::Compare { column, op, value } => format!("{}{:?}{}", column, op, value),
+            Filter::And(a, b) => format!("({} and {})", shape(a), shape(b)),
+            Filter::Or(a, b) => format!("({} or {})", shape(a), shape(b)),
+        }
+    }
+
+    fn parsed(expr: &str) -> String {
+        shape(&parse(expr).unwrap())
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(parsed("name == a or age > 1 and city == x"), "(0Eqa or (1Gt1 and 2Eqx))");
+        assert_eq!(parsed("name == a and age > 1 or city == x"), "((0Eqa and 1Gt1) or 2Eqx)");
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        assert_eq!(parsed("(name == a or age > 1) and city == x"), "((0Eqa or 1Gt1) and 2Eqx)");
+        assert_eq!(parsed("((name == a))"), "0Eqa");
+    }
+
+    #[test]
+    fn chains_group_from_the_left() {
+        assert_eq!(parsed("name == a or name == b or name == c"), "((0Eqa or 0Eqb) or 0Eqc)");
+        assert_eq!(parsed("age > 1 and age < 9 and city != x"), "((1Gt1 and 1Lt9) and 2Nex)");
+    }
+
+    #[test]
+    fn operators_and_quoting() {
+        assert_eq!(parsed("age>=30 AND age<=40"), "(1Ge30 and 1Le40)");
+        assert_eq!(parsed("city CONTAINS york"), "2Containsyork");
+        assert_eq!(parsed("city == 'New York' or \"name\" != \"a b\""), "(2EqNew York or 0Nea b)");
+        assert_eq!(parsed("1 == 'x or y'"), "1Eqx or y");
+    }
+
+    #[test]
+    fn malformed_expressions() {
+        let cases = [
+            ("", "Expected a column name at end of filter expression"),
+            ("name", "Expected an operator after 'name'"),
+            ("name ==", "Expected a value at end of filter expression"),
+            ("name = a", "Unknown operator '=' in filter expression"),
+            ("name ! a", "Unknown operator '!' in filter expression"),
+            ("name == 'a", "Unterminated quote in filter expression"),
+            ("name a", "Expected an operator after 'name', found 'a'"),
+            ("name == ==", "Expected a value after operator, found operator Eq"),
+            ("== a", "Expected a column name, found operator Eq"),
+            ("(name == a", "Missing ')' in filter expression"),
+            ("name == a)", "Unexpected ')' in filter expression"),
+            ("name == a age > 1", "Unexpected 'age' in filter expression"),
+            ("name == a and", "Expected a column name at end of filter expression"),
+            ("nmae == a", "Column 'nmae' not found in headers"),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(parse(expr).unwrap_err(), expected, "for {:?}", expr);
+        }
+    }
+
+    #[test]
+    fn compares_numbers_numerically_and_text_as_text() {
+        let filter = parse("age > 9 and name < b").unwrap();
+        assert!(filter.matches(&StringRecord::from(vec!["ann", "10", "x"]), None));
+        assert!(!filter.matches(&StringRecord::from(vec!["bob", "10", "x"]), None));
+        assert!(!filter.matches(&StringRecord::from(vec!["ann", "8", "x"]), None));
+        // A short row's missing cells compare as ""
+        assert!(parse("city == ''").unwrap().matches(&StringRecord::from(vec!["ann"]), None));
+    }
+}
@@ -0,0 +1,783 @@
+use csv::{Reader, StringRecord};
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    error::Error,
+    io::Read,
+    str::FromStr,
+};
+
+mod diff;
+mod filter;
+mod schema;
+mod template;
+
+pub use diff::{CellChange, Diff, RowChange, diff};
+pub use filter::{Filter, Op};
+pub use schema::{ColumnSchema, ColumnType, infer_schema};
+pub use template::Template;
+
+/// A default set of symbols for [`parse_number`] to strip: currency signs
+/// and `%`.
+pub const NUMBER_SYMBOLS: &[char] = &['$', '€', '£', '¥', '%'];
+
+/// The one notion of "this cell is a number" behind stats, aggregates and
+/// filter comparisons. Surrounding whitespace is ignored, and so are values
+/// that aren't finite, such as `NaN`, `inf` or an overflowing `1e999`, which
+/// are names or typos far more often than data. With `symbols`, any of them
+/// are stripped from both ends of the cell, so with [`NUMBER_SYMBOLS`]
+/// `$5`, `12%` and `5$` all parse; a minus sign may come before or after
+/// them, and commas are accepted as thousands separators, but only in
+/// groups of three. `None` parses strictly.
+pub fn parse_number(cell: &str, symbols: Option<&[char]>) -> Option<f64> {
+    let cell = cell.trim();
+    if let Ok(value) = cell.parse::<f64>() {
+        return value.is_finite().then_some(value);
+    }
+    let symbols = symbols?;
+
+    let (negative, rest) = match cell.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, cell),
+    };
+    let rest = rest.trim_matches(symbols).trim();
+    // "$-5" as well as "-$5"
+    let (negative, rest) = match rest.strip_prefix('-') {
+        Some(rest) if !negative => (true, rest),
+        _ => (negative, rest),
+    };
+
+    let (int, frac) = rest.split_once('.').map_or((rest, None), |(int, frac)| (int, Some(frac)));
+    let mut groups = int.split(',');
+    let first = groups.next()?;
+    if (int.contains(',') && !(1..=3).contains(&first.len())) || groups.any(|group| group.len() != 3) {
+        return None;
+    }
+    let digits = int.replace(',', "");
+    let frac = frac.unwrap_or("0");
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !all_digits(&digits) || !all_digits(frac) {
+        return None;
+    }
+    let value: f64 = format!("{}.{}", digits, frac).parse().ok()?;
+    value.is_finite().then_some(if negative { -value } else { value })
+}
+
+/// How [`resolve_column`] matches a column spec to the headers.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ColumnOptions {
+    /// Which of several headers sharing a name to take (1-based); ignored
+    /// for unique names and numeric specs.
+    pub occurrence: Option<usize>,
+    /// Compare names ignoring case.
+    pub ignore_case: bool,
+    /// Called with a warning when several headers match a name ignoring
+    /// case and the first is taken.
+    pub on_collision: Option<fn(&str)>,
+}
+
+/// Resolves a column spec to its index: a 0-based number or a header name.
+///
+/// A name shared by several headers is an error unless `options.occurrence`
+/// picks one of the matches. With `options.ignore_case`, a name matches every
+/// header equal to it ignoring case; when several do and no occurrence is
+/// given, the first is taken and `options.on_collision` is told why.
+pub fn resolve_column(headers: &StringRecord, spec: &str, options: ColumnOptions) -> Result<usize, Box<dyn Error>> {
+    let spec = spec.trim();
+    if let Ok(idx) = spec.parse::<usize>() {
+        if idx >= headers.len() {
+            return Err(format!("Column index {} out of range (0-{})",
+                idx, headers.len().saturating_sub(1)).into());
+        }
+        return Ok(idx);
+    }
+
+    let folded = spec.to_lowercase();
+    let matches: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .filter(|(_, h)| if options.ignore_case { h.to_lowercase() == folded } else { *h == spec })
+        .map(|(idx, _)| idx)
+        .collect();
+
+    if options.ignore_case && matches.len() > 1 && options.occurrence.is_none() {
+        if let Some(warn) = options.on_collision {
+            let names: Vec<String> = matches.iter().map(|&idx| format!("'{}' ({})", &headers[idx], idx)).collect();
+            warn(&format!("column '{}' matches {} ignoring case; using the first", spec, names.join(", ")));
+        }
+        return Ok(matches[0]);
+    }
+
+    match (matches.as_slice(), options.occurrence) {
+        ([], _) => Err(format!("Column '{}' not found in headers", spec).into()),
+        ([idx], _) => Ok(*idx),
+        (_, Some(n)) => match n.checked_sub(1).and_then(|i| matches.get(i)) {
+            Some(&idx) => Ok(idx),
+            None => Err(format!("Column '{}' occurs {} time(s); occurrence {} does not exist",
+                spec, matches.len(), n).into()),
+        },
+        (_, None) => {
+            let indices: Vec<String> = matches.iter().map(|idx| idx.to_string()).collect();
+            Err(format!("Column '{}' is ambiguous: it matches indices {}; select it by index instead",
+                spec, indices.join(", ")).into())
+        }
+    }
+}
+
+/// Resolves a comma-separated list of column specs, keeping their order.
+pub fn resolve_columns(headers: &StringRecord, specs: &str, options: ColumnOptions) -> Result<Vec<usize>, Box<dyn Error>> {
+    specs.split(',').map(|spec| resolve_column(headers, spec, options)).collect()
+}
+
+/// Yields each record projected onto `indices`, padding missing fields with "".
+pub fn extract<'a, R: Read>(
+    reader: &'a mut Reader<R>,
+    indices: &'a [usize],
+) -> impl Iterator<Item = Result<StringRecord, csv::Error>> + 'a {
+    reader
+        .records()
+        .map(move |result| Ok(project(&result?, indices)))
+}
+
+/// Picks the fields at `indices` out of `record`, padding missing fields with "".
+pub fn project(record: &StringRecord, indices: &[usize]) -> StringRecord {
+    indices
+        .iter()
+        .map(|&idx| record.get(idx).unwrap_or(""))
+        .collect()
+}
+
+/// Running aggregates for a column whose non-empty cells all parse as numbers.
+#[derive(Clone, Copy, Serialize)]
+pub struct NumericStats {
+    pub count: usize,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub p25: f64,
+    pub p75: f64,
+}
+
+impl NumericStats {
+    fn push(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.mean = self.sum / self.count as f64;
+    }
+}
+
+pub struct ColumnStats {
+    pub name: String,
+    pub counts: HashMap<String, usize>,
+    pub rows: usize,
+    pub empty_cells: usize,
+    /// `None` once a non-numeric value is seen or if the column has no values.
+    pub numeric: Option<NumericStats>,
+    numeric_candidate: bool,
+    // Kept only while the column still looks numeric, for the percentiles.
+    values: Vec<f64>,
+}
+
+impl ColumnStats {
+    fn new(name: &str) -> Self {
+        ColumnStats {
+            name: name.to_string(),
+            counts: HashMap::new(),
+            rows: 0,
+            empty_cells: 0,
+            numeric: None,
+            numeric_candidate: true,
+            values: Vec::new(),
+        }
+    }
+
+    // Null markers count as empty but stay out of the value counts and numerics.
+    fn record(&mut self, field: &str, is_null: bool, symbols: Option<&[char]>) {
+        self.rows += 1;
+        if field.is_empty() || is_null {
+            self.empty_cells += 1;
+        }
+        if is_null {
+            return;
+        }
+        *self.counts.entry(field.to_string()).or_insert(0) += 1;
+        self.record_numeric(field, symbols);
+    }
+
+    /// The `n` most frequent values, ties broken alphabetically.
+    pub fn top_values(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut values: Vec<(&str, usize)> = self
+            .counts
+            .iter()
+            .map(|(val, count)| (val.as_str(), *count))
+            .collect();
+        sort_by_frequency(&mut values);
+        values.truncate(n);
+        values
+    }
+
+    pub fn unique_values(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Ties go to the alphabetically first value, as in [`sort_by_frequency`],
+    /// so repeated runs agree.
+    pub fn most_common(&self) -> Option<(&str, usize)> {
+        self.counts
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+            .map(|(val, count)| (val.as_str(), *count))
+    }
+
+    fn record_numeric(&mut self, field: &str, symbols: Option<&[char]>) {
+        if !self.numeric_candidate || field.is_empty() {
+            return;
+        }
+        match parse_number(field, symbols) {
+            Some(value) => {
+                self.values.push(value);
+                self.numeric
+                    .get_or_insert(NumericStats {
+                        count: 0,
+                        sum: 0.0,
+                        min: f64::INFINITY,
+                        max: f64::NEG_INFINITY,
+                        mean: 0.0,
+                        median: 0.0,
+                        p25: 0.0,
+                        p75: 0.0,
+                    })
+                    .push(value);
+            }
+            None => {
+                self.numeric_candidate = false;
+                self.numeric = None;
+                self.values = Vec::new();
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        let mut values = std::mem::take(&mut self.values);
+        if let Some(numeric) = self.numeric.as_mut() {
+            values.sort_by(f64::total_cmp);
+            numeric.median = percentile(&values, 0.5);
+            numeric.p25 = percentile(&values, 0.25);
+            numeric.p75 = percentile(&values, 0.75);
+        }
+    }
+}
+
+/// Most frequent first, ties broken alphabetically so output is stable.
+fn sort_by_frequency<S: Ord>(values: &mut [(S, usize)]) {
+    values.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+}
+
+/// Linearly interpolated percentile of already-sorted values; 0.0 when empty.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let pos = p * (sorted.len() - 1) as f64;
+    let lo = pos.floor() as usize;
+    let hi = pos.ceil() as usize;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+impl Serialize for ColumnStats {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct MostCommon<'a> {
+            value: &'a str,
+            count: usize,
+        }
+
+        let mut state = serializer.serialize_struct("ColumnStats", 5)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("unique_values", &self.unique_values())?;
+        state.serialize_field("empty_cells", &self.empty_cells)?;
+        state.serialize_field(
+            "most_common",
+            &self.most_common().map(|(value, count)| MostCommon { value, count }),
+        )?;
+        state.serialize_field("numeric", &self.numeric)?;
+        state.end()
+    }
+}
+
+pub struct Stats {
+    pub row_count: usize,
+    pub empty_cells: usize,
+    pub columns: Vec<ColumnStats>,
+}
+
+impl Stats {
+    pub fn column_count(&self) -> usize {
+        self.columns.len()
+    }
+}
+
+impl Serialize for Stats {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Stats", 4)?;
+        state.serialize_field("row_count", &self.row_count)?;
+        state.serialize_field("column_count", &self.column_count())?;
+        state.serialize_field("empty_cells", &self.empty_cells)?;
+        state.serialize_field("columns", &self.columns)?;
+        state.end()
+    }
+}
+
+fn is_null(field: &str, null_values: &[String]) -> bool {
+    null_values.iter().any(|null| null == field)
+}
+
+/// Scans every record once, counting rows, empty cells and per-column values.
+/// Cells equal to one of `null_values` are treated as empty; `symbols` is
+/// passed on to [`parse_number`].
+pub fn compute_stats<R: Read>(
+    reader: &mut Reader<R>,
+    null_values: &[String],
+    symbols: Option<&[char]>,
+) -> Result<Stats, Box<dyn Error>> {
+    let headers = reader.headers()?.clone();
+    let mut stats = Stats {
+        row_count: 0,
+        empty_cells: 0,
+        columns: headers.iter().map(ColumnStats::new).collect(),
+    };
+
+    for result in reader.records() {
+        let record = result?;
+        stats.row_count += 1;
+
+        for (i, field) in record.iter().enumerate() {
+            let is_null = is_null(field, null_values);
+            if field.is_empty() || is_null {
+                stats.empty_cells += 1;
+            }
+
+            // Count unique values for each column
+            if let Some(column) = stats.columns.get_mut(i) {
+                column.record(field, is_null, symbols);
+            }
+        }
+    }
+
+    for column in &mut stats.columns {
+        column.finish();
+    }
+
+    Ok(stats)
+}
+
+/// Like `compute_stats`, but only tracks the column at `index`.
+pub fn compute_column_stats<R: Read>(
+    reader: &mut Reader<R>,
+    index: usize,
+    null_values: &[String],
+    symbols: Option<&[char]>,
+) -> Result<ColumnStats, Box<dyn Error>> {
+    let headers = reader.headers()?.clone();
+    let mut column = ColumnStats::new(headers.get(index).unwrap_or(""));
+
+    for result in reader.records() {
+        let record = result?;
+        let field = record.get(index).unwrap_or("");
+        column.record(field, is_null(field, null_values), symbols);
+    }
+
+    column.finish();
+    Ok(column)
+}
+
+/// Counts each distinct value of the column at `index`, most frequent first.
+/// Fails as soon as more than `max_values` distinct values turn up, rather
+/// than growing without bound on an ID-like column.
+pub fn count_values<R: Read>(
+    reader: &mut Reader<R>,
+    index: usize,
+    max_values: Option<usize>,
+) -> Result<Vec<(String, usize)>, Box<dyn Error>> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for result in reader.records() {
+        let record = result?;
+        *counts.entry(record.get(index).unwrap_or("").to_string()).or_insert(0) += 1;
+        check_distinct(counts.len(), max_values)?;
+    }
+
+    let mut values: Vec<(String, usize)> = counts.into_iter().collect();
+    sort_by_frequency(&mut values);
+    Ok(values)
+}
+
+/// Errors once `distinct` goes over `max`.
+pub fn check_distinct(distinct: usize, max: Option<usize>) -> Result<(), Box<dyn Error>> {
+    match max {
+        Some(max) if distinct > max => Err(format!("More than {} distinct values", max).into()),
+        _ => Ok(()),
+    }
+}
+
+/// A row that failed validation. `row` is 1-based over the data rows.
+#[derive(Serialize)]
+pub struct Problem {
+    pub row: usize,
+    /// Stable reason code: `too_few_fields`, `too_many_fields` or `missing_required`.
+    pub code: &'static str,
+    pub detail: String,
+}
+
+#[derive(Serialize)]
+pub struct Validation {
+    pub rows_checked: usize,
+    pub problems: Vec<Problem>,
+}
+
+impl Validation {
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Checks that every row has one field per header and a value in each of the
+/// `required` columns. The reader must be flexible to see ragged rows.
+pub fn validate<R: Read>(reader: &mut Reader<R>, required: &[usize]) -> Result<Validation, Box<dyn Error>> {
+    let headers = reader.headers()?.clone();
+    let mut validation = Validation { rows_checked: 0, problems: Vec::new() };
+
+    for result in reader.records() {
+        let record = result?;
+        validation.rows_checked += 1;
+        let row = validation.rows_checked;
+
+        if record.len() != headers.len() {
+            validation.problems.push(Problem {
+                row,
+                code: if record.len() < headers.len() { "too_few_fields" } else { "too_many_fields" },
+                detail: format!("expected {} fields, found {}", headers.len(), record.len()),
+            });
+        }
+        for &idx in required {
+            if record.get(idx).unwrap_or("").trim().is_empty() {
+                validation.problems.push(Problem {
+                    row,
+                    code: "missing_required",
+                    detail: format!("column '{}' is empty", &headers[idx]),
+                });
+            }
+        }
+    }
+
+    Ok(validation)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Count,
+    Sum,
+    Mean,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    pub fn name(self) -> &'static str {
+        match self {
+            Aggregate::Count => "count",
+            Aggregate::Sum => "sum",
+            Aggregate::Mean => "mean",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+        }
+    }
+}
+
+impl FromStr for Aggregate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "count" => Ok(Aggregate::Count),
+            "sum" => Ok(Aggregate::Sum),
+            "mean" => Ok(Aggregate::Mean),
+            "min" => Ok(Aggregate::Min),
+            "max" => Ok(Aggregate::Max),
+            _ => Err(format!("unknown aggregate '{}' (expected count, sum, mean, min or max)", s)),
+        }
+    }
+}
+
+/// Per-group running totals; `count` counts rows, the rest only numeric values.
+#[derive(Default)]
+pub struct Accumulator {
+    pub count: usize,
+    pub numeric_count: usize,
+    pub sum: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl Accumulator {
+    pub fn push(&mut self, value: f64) {
+        self.numeric_count += 1;
+        self.sum += value;
+        self.min = Some(self.min.map_or(value, |m| m.min(value)));
+        self.max = Some(self.max.map_or(value, |m| m.max(value)));
+    }
+
+    /// The aggregate result, or `None` when no numeric values were seen.
+    pub fn result(&self, op: Aggregate) -> Option<f64> {
+        match op {
+            Aggregate::Count => Some(self.count as f64),
+            Aggregate::Sum if self.numeric_count > 0 => Some(self.sum),
+            Aggregate::Mean if self.numeric_count > 0 => Some(self.sum / self.numeric_count as f64),
+            Aggregate::Min => self.min,
+            Aggregate::Max => self.max,
+            _ => None,
+        }
+    }
+}
+
+/// Guesses the delimiter of `sample` (the first lines of a file) among
+/// `,` `;` tab and `|`: the one whose per-line count is most consistent,
+/// preferring more fields. `None` when no candidate stands out.
+pub fn sniff_delimiter(sample: &str) -> Option<u8> {
+    let lines: Vec<&str> = sample.lines().filter(|line| !line.trim().is_empty()).take(10).collect();
+    let mut scores = Vec::new();
+
+    for delimiter in [b',', b';', b'\t', b'|'] {
+        let counts: Vec<usize> = lines.iter().map(|line| count_unquoted(line, delimiter)).collect();
+        let mut frequency: HashMap<usize, usize> = HashMap::new();
+        for &count in counts.iter().filter(|&&count| count > 0) {
+            *frequency.entry(count).or_insert(0) += 1;
+        }
+        // Lines agreeing on the most common count, then that count
+        if let Some((count, lines)) = frequency.into_iter().max_by_key(|&(count, lines)| (lines, count)) {
+            scores.push(((lines, count), delimiter));
+        }
+    }
+
+    scores.sort_by_key(|&(score, _)| Reverse(score));
+    match scores.as_slice() {
+        [] => None,
+        [(_, delimiter)] => Some(*delimiter),
+        [(best, delimiter), (runner_up, _), ..] => (best != runner_up).then_some(*delimiter),
+    }
+}
+
+// Counts `delimiter` outside double-quoted fields.
+fn count_unquoted(line: &str, delimiter: u8) -> usize {
+    let mut in_quotes = false;
+    let mut count = 0;
+    for byte in line.bytes() {
+        if byte == b'"' {
+            in_quotes = !in_quotes;
+        } else if byte == delimiter && !in_quotes {
+            count += 1;
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LENIENT: Option<&[char]> = Some(NUMBER_SYMBOLS);
+
+    fn headers(names: &[&str]) -> StringRecord {
+        StringRecord::from(names.to_vec())
+    }
+
+    #[test]
+    fn resolve_column_by_name() {
+        let headers = headers(&["id", "name", "city"]);
+        assert_eq!(resolve_column(&headers, "city", ColumnOptions::default()).unwrap(), 2);
+        assert_eq!(resolve_column(&headers, " name ", ColumnOptions::default()).unwrap(), 1);
+    }
+
+    #[test]
+    fn resolve_column_by_index() {
+        let headers = headers(&["id", "name", "city"]);
+        assert_eq!(resolve_column(&headers, "0", ColumnOptions::default()).unwrap(), 0);
+        assert_eq!(resolve_column(&headers, "2", ColumnOptions::default()).unwrap(), 2);
+    }
+
+    #[test]
+    fn resolve_column_index_out_of_range() {
+        let headers = headers(&["id", "name", "city"]);
+        let err = resolve_column(&headers, "3", ColumnOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), "Column index 3 out of range (0-2)");
+    }
+
+    #[test]
+    fn resolve_column_missing_name() {
+        let headers = headers(&["id", "name", "city"]);
+        let err = resolve_column(&headers, "email", ColumnOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), "Column 'email' not found in headers");
+    }
+
+    #[test]
+    fn resolve_column_duplicate_name_is_ambiguous() {
+        let headers = headers(&["id", "amount", "note", "amount"]);
+        let err = resolve_column(&headers, "amount", ColumnOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), "Column 'amount' is ambiguous: it matches indices 1, 3; select it by index instead");
+        assert_eq!(resolve_column(&headers, "3", ColumnOptions::default()).unwrap(), 3);
+    }
+
+    #[test]
+    fn resolve_column_duplicate_by_occurrence() {
+        let headers = headers(&["id", "amount", "note", "amount"]);
+        let nth = |n| ColumnOptions { occurrence: Some(n), ..Default::default() };
+        assert_eq!(resolve_column(&headers, "amount", nth(1)).unwrap(), 1);
+        assert_eq!(resolve_column(&headers, "amount", nth(2)).unwrap(), 3);
+        let err = resolve_column(&headers, "amount", nth(3)).unwrap_err();
+        assert_eq!(err.to_string(), "Column 'amount' occurs 2 time(s); occurrence 3 does not exist");
+        // A unique name ignores the occurrence
+        assert_eq!(resolve_column(&headers, "note", nth(2)).unwrap(), 2);
+    }
+
+    #[test]
+    fn resolve_column_ignoring_case() {
+        let headers = headers(&["ID", "Name", "city"]);
+        let options = ColumnOptions { ignore_case: true, ..Default::default() };
+        assert_eq!(resolve_column(&headers, "name", options).unwrap(), 1);
+        assert_eq!(resolve_column(&headers, "CITY", options).unwrap(), 2);
+        assert!(resolve_column(&headers, "name", ColumnOptions::default()).is_err());
+    }
+
+    #[test]
+    fn resolve_column_ignoring_case_takes_the_first_of_a_collision() {
+        let headers = headers(&["id", "Name", "name"]);
+        let options = ColumnOptions { ignore_case: true, ..Default::default() };
+        assert_eq!(resolve_column(&headers, "name", options).unwrap(), 1);
+        let second = ColumnOptions { occurrence: Some(2), ..options };
+        assert_eq!(resolve_column(&headers, "name", second).unwrap(), 2);
+    }
+
+    #[test]
+    fn parse_number_plain() {
+        assert_eq!(parse_number("42", None), Some(42.0));
+        assert_eq!(parse_number(" -3.5 ", None), Some(-3.5));
+        assert_eq!(parse_number("1e3", None), Some(1000.0));
+        assert_eq!(parse_number("", None), None);
+        assert_eq!(parse_number("abc", None), None);
+    }
+
+    #[test]
+    fn parse_number_rejects_non_finite_values() {
+        for cell in ["NaN", "nan", "inf", "-inf", "Infinity", "1e999"] {
+            assert_eq!(parse_number(cell, None), None, "{}", cell);
+            assert_eq!(parse_number(cell, LENIENT), None, "{}", cell);
+        }
+    }
+
+    #[test]
+    fn parse_number_strict_rejects_formatted_numbers() {
+        for cell in ["1,234", "$5.00", "12%"] {
+            assert_eq!(parse_number(cell, None), None, "{}", cell);
+        }
+    }
+
+    #[test]
+    fn parse_number_lenient_formats() {
+        assert_eq!(parse_number("1,234", LENIENT), Some(1234.0));
+        assert_eq!(parse_number("1,234,567.89", LENIENT), Some(1234567.89));
+        assert_eq!(parse_number("$5.00", LENIENT), Some(5.0));
+        assert_eq!(parse_number("€12", LENIENT), Some(12.0));
+        assert_eq!(parse_number("12%", LENIENT), Some(12.0));
+        assert_eq!(parse_number("-$5", LENIENT), Some(-5.0));
+        assert_eq!(parse_number("$-5", LENIENT), Some(-5.0));
+        assert_eq!(parse_number(" $ 1,000 ", LENIENT), Some(1000.0));
+    }
+
+    #[test]
+    fn parse_number_strips_symbols_from_either_end() {
+        assert_eq!(parse_number("%5", LENIENT), Some(5.0));
+        assert_eq!(parse_number("5$", LENIENT), Some(5.0));
+        assert_eq!(parse_number("$5%", LENIENT), Some(5.0));
+    }
+
+    #[test]
+    fn parse_number_only_strips_the_given_symbols() {
+        let points = Some(&['p', 't', 's'][..]);
+        assert_eq!(parse_number("12pts", points), Some(12.0));
+        assert_eq!(parse_number("$12", points), None);
+        assert_eq!(parse_number("1,200", Some(&[][..])), Some(1200.0));
+    }
+
+    #[test]
+    fn parse_number_lenient_rejects_bad_grouping() {
+        for cell in ["1,23", "12,34,567", "1234,567", ",123", "1,", "$", "%", "1.2.3", "--5"] {
+            assert_eq!(parse_number(cell, LENIENT), None, "{}", cell);
+        }
+    }
+
+    #[test]
+    fn sniff_each_delimiter() {
+        assert_eq!(sniff_delimiter("a,b,c\n1,2,3\n4,5,6\n"), Some(b','));
+        assert_eq!(sniff_delimiter("a;b;c\n1;2,5;3\n4;5;6\n"), Some(b';'));
+        assert_eq!(sniff_delimiter("a\tb\n1\t2\n"), Some(b'\t'));
+        assert_eq!(sniff_delimiter("a|b|c\n1|2|3\n"), Some(b'|'));
+    }
+
+    #[test]
+    fn sniff_prefers_the_consistent_delimiter() {
+        // Commas inside the values vary per line; the semicolons don't
+        let sample = "name;note\nAnn;a, b, c\nBob;d\nCy;e, f\n";
+        assert_eq!(sniff_delimiter(sample), Some(b';'));
+    }
+
+    #[test]
+    fn sniff_ignores_delimiters_in_quotes() {
+        let sample = "a;b\n\"1,2,3\";x\n\"4,5\";y\n";
+        assert_eq!(sniff_delimiter(sample), Some(b';'));
+    }
+
+    #[test]
+    fn sniff_ambiguous_or_empty() {
+        assert_eq!(sniff_delimiter("a,b;c\n1,2;3\n"), None);
+        assert_eq!(sniff_delimiter("single\ncolumn\n"), None);
+        assert_eq!(sniff_delimiter(""), None);
+    }
+
+    #[test]
+    fn stats_json_has_the_dimensions() {
+        let mut reader = csv::ReaderBuilder::new().from_reader("a,b\n1,\n2,x\n".as_bytes());
+        let stats = compute_stats(&mut reader, &[], None).unwrap();
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["row_count"], 2);
+        assert_eq!(json["column_count"], 2);
+        assert_eq!(json["empty_cells"], 1);
+        assert_eq!(json["columns"][1]["name"], "b");
+        assert_eq!(json["columns"][1]["most_common"]["value"], "");
+    }
+
+    #[test]
+    fn most_common_breaks_ties_alphabetically() {
+        for _ in 0..20 {
+            let mut reader = csv::ReaderBuilder::new().from_reader("v\nb\nc\na\nc\na\n".as_bytes());
+            let stats = compute_stats(&mut reader, &[], None).unwrap();
+            assert_eq!(stats.columns[0].most_common(), Some(("a", 2)));
+        }
+    }
+
+    #[test]
+    fn resolve_columns_keeps_order_and_trims() {
+        let headers = headers(&["id", "name", "city"]);
+        assert_eq!(resolve_columns(&headers, "city, 0 ,name", ColumnOptions::default()).unwrap(), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn resolve_columns_fails_on_any_bad_spec() {
+        let headers = headers(&["id", "name", "city"]);
+        let err = resolve_columns(&headers, "id,email", ColumnOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), "Column 'email' not found in headers");
+    }
+}
@@ -0,0 +1,149 @@
+//! Column types inferred from the data, for describing a file as a schema.
+
+use crate::parse_number;
+use csv::Reader;
+use serde::Serialize;
+use std::{error::Error, io::Read};
+
+/// The JSON type a column's cells fit, from narrowest to widest.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Boolean,
+    Integer,
+    Number,
+    String,
+}
+
+impl ColumnType {
+    /// The narrowest type a non-empty cell fits. Numbers are those
+    /// [`parse_number`] accepts; `true` and `false` are booleans in any case.
+    pub fn of(cell: &str, symbols: Option<&[char]>) -> ColumnType {
+        let cell = cell.trim();
+        if cell.eq_ignore_ascii_case("true") || cell.eq_ignore_ascii_case("false") {
+            return ColumnType::Boolean;
+        }
+        match parse_number(cell, symbols) {
+            Some(value) => {
+                if value.fract() == 0.0 && !cell.contains(['.', 'e', 'E']) {
+                    ColumnType::Integer
+                } else {
+                    ColumnType::Number
+                }
+            }
+            None => ColumnType::String,
+        }
+    }
+
+    /// The narrowest type both fit. Integers widen to numbers; any other
+    /// mix is only a string.
+    pub fn widen(self, other: ColumnType) -> ColumnType {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Integer, ColumnType::Number) | (ColumnType::Number, ColumnType::Integer) => ColumnType::Number,
+            _ => ColumnType::String,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColumnType::Boolean => "boolean",
+            ColumnType::Integer => "integer",
+            ColumnType::Number => "number",
+            ColumnType::String => "string",
+        }
+    }
+}
+
+/// What the data shows about one column.
+#[derive(Serialize)]
+pub struct ColumnSchema {
+    pub name: String,
+    /// `None` when every cell is empty.
+    #[serde(rename = "type")]
+    pub kind: Option<ColumnType>,
+    /// Whether any cell is empty or whitespace.
+    pub nullable: bool,
+}
+
+/// Infers every column's type and nullability from all the rows of `reader`.
+/// Cells missing from short rows count as empty; `symbols` is passed on to
+/// [`parse_number`].
+pub fn infer_schema<R: Read>(reader: &mut Reader<R>, symbols: Option<&[char]>) -> Result<Vec<ColumnSchema>, Box<dyn Error>> {
+    let mut columns: Vec<ColumnSchema> = reader
+        .headers()?
+        .iter()
+        .map(|name| ColumnSchema { name: name.to_string(), kind: None, nullable: false })
+        .collect();
+
+    for result in reader.records() {
+        let record = result?;
+        for (idx, column) in columns.iter_mut().enumerate() {
+            let cell = record.get(idx).unwrap_or("");
+            if cell.trim().is_empty() {
+                column.nullable = true;
+                continue;
+            }
+            let kind = ColumnType::of(cell, symbols);
+            column.kind = Some(column.kind.map_or(kind, |seen| seen.widen(kind)));
+        }
+    }
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NUMBER_SYMBOLS;
+    use csv::ReaderBuilder;
+
+    #[test]
+    fn cell_types() {
+        assert_eq!(ColumnType::of("42", None), ColumnType::Integer);
+        assert_eq!(ColumnType::of("-7", None), ColumnType::Integer);
+        assert_eq!(ColumnType::of("4.0", None), ColumnType::Number);
+        assert_eq!(ColumnType::of("1e3", None), ColumnType::Number);
+        assert_eq!(ColumnType::of("TRUE", None), ColumnType::Boolean);
+        assert_eq!(ColumnType::of("NaN", None), ColumnType::String);
+        assert_eq!(ColumnType::of("$5", None), ColumnType::String);
+        assert_eq!(ColumnType::of("$5", Some(NUMBER_SYMBOLS)), ColumnType::Integer);
+    }
+
+    #[test]
+    fn widening() {
+        assert_eq!(ColumnType::Integer.widen(ColumnType::Number), ColumnType::Number);
+        assert_eq!(ColumnType::Boolean.widen(ColumnType::Integer), ColumnType::String);
+        assert_eq!(ColumnType::Number.widen(ColumnType::Number), ColumnType::Number);
+    }
+
+    #[test]
+    fn infers_a_mixed_file() {
+        let data = "id,price,active,name,note,blank\n\
+                    1,9.5,true,Ann,,\n\
+                    2,10,false,Bob,x,\n\
+                    3,,TRUE,42,y,\n";
+        let mut reader = ReaderBuilder::new().from_reader(data.as_bytes());
+        let columns = infer_schema(&mut reader, None).unwrap();
+        let summary: Vec<(&str, Option<ColumnType>, bool)> =
+            columns.iter().map(|c| (c.name.as_str(), c.kind, c.nullable)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("id", Some(ColumnType::Integer), false),
+                ("price", Some(ColumnType::Number), true),
+                ("active", Some(ColumnType::Boolean), false),
+                ("name", Some(ColumnType::String), false),
+                ("note", Some(ColumnType::String), true),
+                ("blank", None, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn short_rows_count_as_empty() {
+        let mut reader = ReaderBuilder::new().flexible(true).from_reader("a,b\n1,2\n3\n".as_bytes());
+        let columns = infer_schema(&mut reader, None).unwrap();
+        assert!(!columns[0].nullable);
+        assert!(columns[1].nullable);
+    }
+}
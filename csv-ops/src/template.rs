@@ -0,0 +1,127 @@
+//! Per-row text templates, e.g.
+//! `INSERT INTO users VALUES ('{id}', '{name}');`.
+//!
+//! `{column}` is replaced by the row's cell in that column, given as a header
+//! name or 0-based index; `{{` and `}}` stand for literal braces.
+
+use crate::{ColumnOptions, resolve_column};
+use csv::StringRecord;
+use std::error::Error;
+
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    Column(usize),
+}
+
+#[derive(Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parses `template`, resolving its placeholders against `headers`, so an
+    /// unknown column is an error before any row is read.
+    pub fn parse(template: &str, headers: &StringRecord, columns: ColumnOptions) -> Result<Template, Box<dyn Error>> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let Some(end) = rest.find('}') else {
+                        return Err("Unclosed '{' in template; write '{{' for a literal brace".into());
+                    };
+                    let spec = &rest[..end];
+                    if spec.trim().is_empty() {
+                        return Err("Empty placeholder '{}' in template".into());
+                    }
+                    let column = resolve_column(headers, spec, columns)
+                        .map_err(|e| format!("In template placeholder '{{{}}}': {}", spec, e))?;
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Column(column));
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err("Unmatched '}' in template; write '}}' for a literal brace".into()),
+                _ => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template { parts })
+    }
+
+    /// The template filled in from `record`. Missing cells render as "".
+    pub fn render(&self, record: &StringRecord) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.as_str(),
+                Part::Column(column) => record.get(*column).unwrap_or(""),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> StringRecord {
+        StringRecord::from(vec!["id", "name"])
+    }
+
+    fn parse(template: &str) -> Result<Template, String> {
+        Template::parse(template, &headers(), ColumnOptions::default()).map_err(|e| e.to_string())
+    }
+
+    fn rendered(template: &str) -> String {
+        parse(template).unwrap().render(&StringRecord::from(vec!["7", "Ann"]))
+    }
+
+    #[test]
+    fn named_and_indexed_placeholders() {
+        assert_eq!(rendered("INSERT INTO users VALUES ('{id}', '{name}');"), "INSERT INTO users VALUES ('7', 'Ann');");
+        assert_eq!(rendered("{1} is #{0}"), "Ann is #7");
+        assert_eq!(rendered("{name}{name}"), "AnnAnn");
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(rendered("{{id}} = {id}"), "{id} = 7");
+        assert_eq!(rendered("{{{name}}}"), "{Ann}");
+        assert_eq!(rendered("no placeholders }}"), "no placeholders }");
+    }
+
+    #[test]
+    fn missing_cells_render_empty() {
+        let template = parse("[{id}|{name}]").unwrap();
+        assert_eq!(template.render(&StringRecord::from(vec!["7"])), "[7|]");
+    }
+
+    #[test]
+    fn malformed_templates() {
+        let cases = [
+            ("{nmae}", "In template placeholder '{nmae}': Column 'nmae' not found in headers"),
+            ("{id", "Unclosed '{' in template; write '{{' for a literal brace"),
+            ("id}", "Unmatched '}' in template; write '}}' for a literal brace"),
+            ("{ }", "Empty placeholder '{}' in template"),
+        ];
+        for (template, expected) in cases {
+            assert_eq!(parse(template).unwrap_err(), expected, "for {:?}", template);
+        }
+    }
+}
@@ -1,49 +1,1117 @@
-use clap::Parser;
-use serde::Deserialize;
-use std::{fs, io::{self, Write}};
+use chrono::Local;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use owo_colors::{OwoColorize, Stream};
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
 #[derive(Parser)]
 #[command(name = "Quiz Game")]
 #[command(about = "A simple terminal-based quiz game")]
-struct Cli {}
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
 
-#[derive(Debug, Deserialize)]
+    /// Disable colored output (also honors the NO_COLOR environment variable)
+    #[arg(long)]
+    no_color: bool,
+
+    /// Questions file (.json, .yaml, .yml or .csv)
+    #[arg(short, long, default_value = "questions.json")]
+    file: PathBuf,
+
+    /// Directory a relative questions file is looked up in [default: the current directory]
+    #[arg(long, env = "QUIZ_DIR", value_name = "DIR")]
+    dir: Option<PathBuf>,
+
+    /// Show the high scores for the quiz file and exit
+    #[arg(long)]
+    high_scores: bool,
+
+    /// Only ask questions from this category
+    #[arg(long)]
+    category: Option<String>,
+
+    /// List the available categories and exit
+    #[arg(long)]
+    list_categories: bool,
+
+    /// Only ask questions of this difficulty (easy, medium or hard)
+    #[arg(long)]
+    difficulty: Option<String>,
+
+    /// Ask at most this many questions
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Grade answers read from this file (one per line, or a JSON array) instead of stdin
+    #[arg(long)]
+    answers: Option<PathBuf>,
+
+    /// Seconds to answer each question before it counts as unanswered
+    #[arg(long, value_name = "SECS", conflicts_with = "answers")]
+    timeout: Option<u64>,
+
+    /// Shuffle the order of questions and their options
+    #[arg(long)]
+    shuffle: bool,
+
+    /// Seed for a reproducible shuffle (implies --shuffle)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Minimum percentage for A, B, C and D grades
+    #[arg(long, value_name = "A,B,C,D", value_delimiter = ',', default_values_t = [90.0, 80.0, 70.0, 60.0])]
+    grade_thresholds: Vec<f64>,
+
+    /// Report pass/fail against this percentage instead of a letter grade
+    #[arg(long)]
+    pass_mark: Option<f64>,
+
+    /// Also write the full result of the run to this JSON file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Let "skip" defer a question to the end of the round (skipping it again leaves it unanswered)
+    #[arg(long)]
+    skip: bool,
+
+    /// After the quiz, keep re-asking missed questions until all are answered correctly
+    #[arg(long)]
+    repeat_missed: bool,
+
+    /// Give up on --repeat-missed after this many rounds in total
+    #[arg(long, default_value_t = 5)]
+    max_rounds: usize,
+
+    /// Write a small sample questions file to --file and exit
+    #[arg(long)]
+    create_sample: bool,
+
+    /// Let --create-sample overwrite an existing file
+    #[arg(long, requires = "create_sample")]
+    force: bool,
+
+    /// Print the selected questions with their options and exit, for a paper copy
+    #[arg(long)]
+    print_questions: bool,
+
+    /// Print each selected question's number and correct answer and exit
+    #[arg(long)]
+    print_key: bool,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print a shell completion script (bash, elvish, fish, powershell, zsh)
+    #[command(hide = true)]
+    Completions { shell: Shell },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum QuestionKind {
+    #[default]
+    MultipleChoice,
+    TrueFalse,
+    FreeText,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 struct Question {
     question: String,
+    #[serde(default)]
+    kind: QuestionKind,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     options: Vec<String>,
     answer: String,
+    /// Extra answers accepted for free-text questions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    accepted: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    explanation: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    difficulty: Option<String>,
+    /// Points awarded for a correct answer; 1 when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    points: Option<u32>,
+}
+
+impl Question {
+    fn points(&self) -> usize {
+        self.points.unwrap_or(1) as usize
+    }
+
+    // Reorders multiple-choice options, relabelling them "a) ", "b) ", ...
+    // and moving the answer letter along with its option.
+    fn shuffle_options(&mut self, rng: &mut StdRng) {
+        if self.kind != QuestionKind::MultipleChoice {
+            return;
+        }
+        let Some(answer_index) = letter_index(&self.answer).filter(|&i| i < self.options.len()) else {
+            return;
+        };
+
+        let mut order: Vec<usize> = (0..self.options.len()).collect();
+        order.shuffle(rng);
+
+        self.options = order
+            .iter()
+            .enumerate()
+            .map(|(pos, &orig)| format!("{}) {}", option_letter(pos), strip_label(&self.options[orig])))
+            .collect();
+        let new_pos = order.iter().position(|&orig| orig == answer_index).unwrap_or(0);
+        self.answer = option_letter(new_pos).to_string();
+    }
+
+    fn prompt(&self) -> String {
+        match self.kind {
+            QuestionKind::MultipleChoice => {
+                let letters: Vec<String> = (0..self.options.len()).map(|i| option_letter(i).to_string()).collect();
+                format!("Your answer ({}):", letters.join(" / "))
+            }
+            QuestionKind::TrueFalse => "Your answer (t / f):".to_string(),
+            QuestionKind::FreeText => "Your answer:".to_string(),
+        }
+    }
+
+    /// The correct answer as printed in the answer key.
+    fn key_answer(&self) -> String {
+        match self.kind {
+            QuestionKind::MultipleChoice => letter_index(&self.answer)
+                .and_then(|i| self.options.get(i))
+                .map_or_else(|| self.answer.clone(), |opt| format!("{}) {}", self.answer, strip_label(opt))),
+            QuestionKind::TrueFalse => match parse_true_false(&self.answer.to_lowercase()) {
+                Some(true) => "True".to_string(),
+                Some(false) => "False".to_string(),
+                None => self.answer.clone(),
+            },
+            QuestionKind::FreeText if self.accepted.is_empty() => self.answer.clone(),
+            QuestionKind::FreeText => format!("{} (also accepted: {})", self.answer, self.accepted.join(", ")),
+        }
+    }
+
+    fn is_correct(&self, input: &str) -> bool {
+        match self.kind {
+            QuestionKind::MultipleChoice => input == self.answer,
+            QuestionKind::TrueFalse => match parse_true_false(input) {
+                Some(given) => Some(given) == parse_true_false(&self.answer.to_lowercase()),
+                None => false,
+            },
+            QuestionKind::FreeText => std::iter::once(&self.answer)
+                .chain(&self.accepted)
+                .any(|a| a.trim().to_lowercase() == input),
+        }
+    }
+}
+
+/// One letter per option, a to z.
+const MAX_OPTIONS: usize = 26;
+
+fn option_letter(index: usize) -> char {
+    (b'a' + index as u8) as char
+}
+
+fn letter_index(answer: &str) -> Option<usize> {
+    let mut chars = answer.trim().chars();
+    match (chars.next(), chars.next()) {
+        (Some(c @ 'a'..='z'), None) => Some(c as usize - 'a' as usize),
+        _ => None,
+    }
+}
+
+// "b) Rome" -> "Rome"; options without a letter label are left alone.
+fn strip_label(option: &str) -> &str {
+    match option.split_once(") ") {
+        Some((label, rest)) if letter_index(label).is_some() => rest,
+        _ => option,
+    }
+}
+
+fn shuffle_questions(questions: &mut [Question], seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    questions.shuffle(&mut rng);
+    for q in questions.iter_mut() {
+        q.shuffle_options(&mut rng);
+    }
+}
+
+fn parse_true_false(input: &str) -> Option<bool> {
+    match input {
+        "t" | "true" => Some(true),
+        "f" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScoreEntry {
+    score: usize,
+    total: usize,
+    percentage: f64,
+    quiz: String,
+    timestamp: String,
+}
+
+const SCORES_PATH: &str = "scores.json";
+
+/// One spreadsheet row: `question, option_a..option_d, answer[, category, explanation]`.
+#[derive(Deserialize)]
+struct CsvQuestion {
+    question: String,
+    #[serde(default)]
+    option_a: String,
+    #[serde(default)]
+    option_b: String,
+    #[serde(default)]
+    option_c: String,
+    #[serde(default)]
+    option_d: String,
+    answer: String,
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    explanation: Option<String>,
+}
+
+// Blank option columns are dropped and the rest re-lettered, so the answer
+// letter, which names a column, is moved along with its option.
+impl TryFrom<CsvQuestion> for Question {
+    type Error = String;
+
+    fn try_from(row: CsvQuestion) -> Result<Self, String> {
+        let columns = [row.option_a, row.option_b, row.option_c, row.option_d];
+        let kept: Vec<usize> = (0..columns.len()).filter(|&i| !columns[i].trim().is_empty()).collect();
+        let options = kept
+            .iter()
+            .enumerate()
+            .map(|(i, &column)| format!("{}) {}", option_letter(i), columns[column].trim()))
+            .collect();
+
+        let mut answer = row.answer.trim().to_lowercase();
+        if let Some(column) = letter_index(&answer).filter(|&i| i < columns.len()) {
+            match kept.iter().position(|&c| c == column) {
+                Some(i) => answer = option_letter(i).to_string(),
+                None => return Err(format!("answer '{}' is a blank option", answer)),
+            }
+        }
+
+        Ok(Question {
+            question: row.question,
+            kind: QuestionKind::MultipleChoice,
+            options,
+            answer,
+            accepted: Vec::new(),
+            explanation: row.explanation.filter(|e| !e.is_empty()),
+            category: row.category.filter(|c| !c.is_empty()),
+            difficulty: None,
+            points: None,
+        })
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+    Correct,
+    Incorrect,
+    Skipped,
+    Unanswered,
+}
+
+/// How one question went on the first attempt, for `--output`.
+#[derive(Serialize)]
+struct QuestionResult {
+    number: usize,
+    question: String,
+    outcome: Outcome,
+    given: Option<String>,
+    answer: String,
+    points: usize,
+}
+
+#[derive(Serialize)]
+struct QuizResult {
+    quiz: String,
+    timestamp: String,
+    score: usize,
+    total: usize,
+    percentage: f64,
+    seconds: f64,
+    questions: Vec<QuestionResult>,
+}
+
+/// The `[quiz]` table of the shared config file. Flags and environment
+/// variables override these.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    dir: Option<PathBuf>,
+    no_color: bool,
+}
+
+/// Set by the Ctrl-C handler.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The quiz was interrupted with Ctrl-C.
+struct Interrupted;
+
+/// One event from the terminal.
+enum Input {
+    Line(String),
+    Eof,
+    Interrupted,
+}
+
+/// Where answers come from: the `--answers` file, or lines typed at the
+/// prompt. Typed lines are read on a background thread, so Ctrl-C and
+/// `--timeout` don't have to wait for Enter.
+enum AnswerSource {
+    Batch(std::vec::IntoIter<String>),
+    Typed {
+        input: Receiver<Input>,
+        closed: bool,
+        timeout: Option<Duration>,
+    },
+}
+
+impl AnswerSource {
+    fn typed(timeout: Option<Duration>) -> Self {
+        let (tx, input) = mpsc::channel();
+        let interrupt = tx.clone();
+        // Without a handler the default action kills the process before
+        // the score is shown
+        if let Err(e) = ctrlc::set_handler(move || {
+            INTERRUPTED.store(true, Ordering::Relaxed);
+            let _ = interrupt.send(Input::Interrupted);
+        }) {
+            eprintln!("Cannot install the Ctrl-C handler: {}", e);
+        }
+        thread::spawn(move || {
+            loop {
+                let mut line = String::new();
+                let event = match io::stdin().read_line(&mut line) {
+                    Ok(0) | Err(_) => Input::Eof,
+                    Ok(_) => Input::Line(line),
+                };
+                let eof = matches!(event, Input::Eof);
+                if tx.send(event).is_err() || eof {
+                    break;
+                }
+            }
+        });
+        AnswerSource::Typed { input, closed: false, timeout }
+    }
+}
+
+struct Missed<'a> {
+    question: &'a Question,
+    given: String,
 }
 
 fn main() {
-    let _cli = Cli::parse();
+    let cli = Cli::parse();
+    let config: Config = shared_config::load("quiz").unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    if cli.no_color || config.no_color {
+        owo_colors::set_override(false);
+    }
+
+    if let Some(Commands::Completions { shell }) = cli.command {
+        clap_complete::generate(shell, &mut Cli::command(), "quize-app", &mut io::stdout());
+        return;
+    }
+
+    // An absolute --file ignores the directory
+    let file = match cli.dir.or(config.dir) {
+        Some(dir) => dir.join(&cli.file),
+        None => cli.file,
+    };
+    let quiz_name = file.display().to_string();
+
+    if cli.create_sample {
+        if file.exists() && !cli.force {
+            eprintln!("{} already exists. Use --force to overwrite it.", quiz_name);
+            process::exit(1);
+        }
+        if let Err(e) = save_questions(&file, &sample_questions()) {
+            eprintln!("Cannot write {}: {}", quiz_name, e);
+            process::exit(1);
+        }
+        println!("Wrote sample questions to {}.", quiz_name);
+        return;
+    }
+
+    if cli.high_scores {
+        print_high_scores(&quiz_name);
+        return;
+    }
+
+    let mut questions = match load_questions(&file) {
+        Ok(questions) => questions,
+        Err(e) => {
+            eprintln!("Cannot load {}: {}", quiz_name, e);
+            process::exit(1);
+        }
+    };
+    if let Err(e) = check_questions(&mut questions) {
+        eprintln!("Invalid {}: {}", quiz_name, e);
+        process::exit(1);
+    }
 
-    let data = fs::read_to_string("questions.json").expect("Cannot read questions.json");
-    let questions: Vec<Question> = serde_json::from_str(&data).expect("Invalid JSON format");
+    if cli.list_categories {
+        list_categories(&questions);
+        return;
+    }
+
+    if let Some(category) = &cli.category {
+        questions.retain(|q| q.category.as_deref() == Some(category.as_str()));
+        if questions.is_empty() {
+            eprintln!(
+                "No questions found in category '{}'. Use --list-categories to see what's available.",
+                category
+            );
+            process::exit(1);
+        }
+    }
+
+    if let Some(difficulty) = &cli.difficulty {
+        questions.retain(|q| {
+            q.difficulty
+                .as_deref()
+                .is_some_and(|d| d.eq_ignore_ascii_case(difficulty))
+        });
+        if questions.is_empty() {
+            eprintln!("No questions found with difficulty '{}'.", difficulty);
+            process::exit(1);
+        }
+    }
+
+    if cli.shuffle || cli.seed.is_some() {
+        let seed = cli.seed.unwrap_or_else(|| {
+            let seed = rand::rng().random();
+            println!("Shuffle seed: {} (use --seed {} to repeat this order)", seed, seed);
+            seed
+        });
+        shuffle_questions(&mut questions, seed);
+    }
+
+    if let Some(limit) = cli.limit {
+        questions.truncate(limit);
+    }
+
+    // After filtering and shuffling, so a --seed gives a matching sheet and key
+    if cli.print_questions || cli.print_key {
+        if cli.print_questions {
+            print_questions(&questions);
+        }
+        if cli.print_questions && cli.print_key {
+            println!();
+        }
+        if cli.print_key {
+            print_key(&questions);
+        }
+        return;
+    }
+
+    let mut source = match &cli.answers {
+        Some(path) => match load_answers(path) {
+            Ok(answers) => AnswerSource::Batch(answers.into_iter()),
+            Err(e) => {
+                eprintln!("Cannot load {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        },
+        None => AnswerSource::typed(cli.timeout.map(Duration::from_secs)),
+    };
 
     let mut score = 0;
+    let total: usize = questions.iter().map(Question::points).sum();
+    let mut missed: Vec<Missed> = Vec::new();
 
     println!("Welcome to the Quiz Game! \n");
+    println!("You will be asked {} questions.\n", questions.len());
+
+    // Each round asks the questions missed in the one before; only the first
+    // round counts towards the score
+    let mut round: Vec<&Question> = questions.iter().collect();
+    let mut round_number = 1;
+    let mut skipped = 0;
+    let mut results: Vec<QuestionResult> = Vec::new();
+    let started = Instant::now();
+    let mut rng = match cli.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_rng(&mut rand::rng()),
+    };
+
+    let mut interrupted = false;
+    let still_missed = loop {
+        let mut wrong = Vec::new();
+        let mut answered = false;
+
+        // A skipped question goes to the back of the round, but only once
+        let mut queue: VecDeque<(usize, &Question, bool)> =
+            round.iter().enumerate().map(|(i, &q)| (i + 1, q, false)).collect();
+
+        while let Some((number, q, deferred)) = queue.pop_front() {
+            let note = if deferred { " (skipped earlier)" } else { "" };
+            println!("Question {} of {}{}", number, round.len(), note);
+            println!("{}. {}", number, q.question);
+            for opt in &q.options {
+                println!("{}", opt);
+            }
+
+            let Ok(mut user_answer) = read_answer(q, &mut source) else {
+                interrupted = true;
+                break;
+            };
+            answered |= user_answer.is_some();
+
+            if cli.skip && user_answer.as_deref() == Some("skip") {
+                if !deferred {
+                    println!("Skipped; it will come back at the end.\n");
+                    skipped += 1;
+                    queue.push_back((number, q, true));
+                    continue;
+                }
+                // Skipped twice: there's no third chance
+                user_answer = None;
+            }
+            let skipped_twice = deferred && user_answer.is_none() && cli.skip;
+            let given = user_answer.clone();
+
+            let correct = match user_answer {
+                Some(user_answer) if q.is_correct(&user_answer) => {
+                    println!("{}\n", "✅ Correct!".if_supports_color(Stream::Stdout, |s| s.green()));
+                    if round_number == 1 {
+                        score += q.points();
+                    }
+                    true
+                }
+                Some(user_answer) => {
+                    let message = format!("❌ Wrong! Correct answer: {}", q.answer);
+                    println!("{}\n", message.if_supports_color(Stream::Stdout, |s| s.red()));
+                    if round_number == 1 {
+                        missed.push(Missed { question: q, given: user_answer });
+                    }
+                    false
+                }
+                None => {
+                    let message = format!("❌ Unanswered! Correct answer: {}", q.answer);
+                    println!("{}\n", message.if_supports_color(Stream::Stdout, |s| s.red()));
+                    if round_number == 1 {
+                        missed.push(Missed { question: q, given: "(unanswered)".to_string() });
+                    }
+                    false
+                }
+            };
+            if !correct {
+                wrong.push(q);
+            }
+
+            if round_number == 1 {
+                let outcome = match (&given, correct) {
+                    (_, true) => Outcome::Correct,
+                    (Some(_), false) => Outcome::Incorrect,
+                    (None, _) if skipped_twice => Outcome::Skipped,
+                    (None, _) => Outcome::Unanswered,
+                };
+                results.push(QuestionResult {
+                    number,
+                    question: q.question.clone(),
+                    outcome,
+                    given,
+                    answer: q.answer.clone(),
+                    points: if correct { q.points() } else { 0 },
+                });
+            }
+        }
+
+        // Running out of batch answers would otherwise re-ask forever
+        if interrupted || !cli.repeat_missed || wrong.is_empty() || !answered || round_number >= cli.max_rounds {
+            break wrong.len();
+        }
+
+        round_number += 1;
+        wrong.shuffle(&mut rng);
+        println!("Round {}: {} missed questions to retry.\n", round_number, wrong.len());
+        round = wrong;
+    };
+
+    if interrupted {
+        println!("\nQuiz interrupted after {} of {} questions. Your Score: {}/{}",
+            results.len(), questions.len(), score, total);
+    } else {
+        println!("Quiz Complete! Your Score: {}/{}", score, total);
+    }
+    if skipped > 0 {
+        println!("Questions skipped: {}", skipped);
+    }
+    if cli.repeat_missed && round_number > 1 {
+        if still_missed == 0 {
+            println!("First-attempt score; every question was answered correctly after {} rounds.", round_number);
+        } else {
+            println!("First-attempt score; {} questions were still missed after {} rounds.", still_missed, round_number);
+        }
+    }
+
+    let pct = percentage(score, total);
+    let (result, passed) = match cli.pass_mark {
+        Some(mark) if pct >= mark => ("PASS", true),
+        Some(_) => ("FAIL", false),
+        None => {
+            let grade = letter_grade(pct, &cli.grade_thresholds);
+            (grade, grade != "F")
+        }
+    };
+    println!("{}", colorize(&format!("Percentage: {:.1}% - {}", pct, result), passed));
+
+    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+    if let Some(path) = &cli.output {
+        results.sort_by_key(|r| r.number);
+        let result = QuizResult {
+            quiz: quiz_name.clone(),
+            timestamp: timestamp.clone(),
+            score,
+            total,
+            percentage: pct,
+            seconds: started.elapsed().as_secs_f64(),
+            questions: results,
+        };
+        if let Err(e) = save_result(path, &result) {
+            eprintln!("Cannot write {}: {}", path.display(), e);
+        }
+    }
 
+    // A partial run would crowd the high scores with incomparable results
+    if !interrupted {
+        let mut scores = load_scores();
+        scores.push(ScoreEntry {
+            score,
+            total,
+            percentage: pct,
+            quiz: quiz_name,
+            timestamp,
+        });
+        save_scores(&scores);
+    }
+
+    if !missed.is_empty() {
+        println!("\nQuestions you missed:");
+        for m in &missed {
+            println!("- {}", m.question.question);
+            println!("  Your answer: {}", m.given);
+            println!("  Correct answer: {}", m.question.answer);
+            if let Some(explanation) = &m.question.explanation {
+                println!("  Explanation: {}", explanation);
+            }
+        }
+    }
+
+    if interrupted {
+        process::exit(130);
+    }
+}
+
+// Catches authoring mistakes before the quiz starts: every answer must be
+// one the player can actually give. Multiple-choice letters are normalized
+// to lowercase so "B" in the file matches "b" at the prompt.
+fn check_questions(questions: &mut [Question]) -> Result<(), String> {
+    for (i, q) in questions.iter_mut().enumerate() {
+        let number = i + 1;
+        match q.kind {
+            QuestionKind::MultipleChoice => {
+                if q.options.len() > MAX_OPTIONS {
+                    return Err(format!("question {}: {} options, but letters only go up to {}",
+                        number, q.options.len(), MAX_OPTIONS));
+                }
+                q.answer = q.answer.trim().to_lowercase();
+                match letter_index(&q.answer) {
+                    None => {
+                        return Err(format!("question {}: answer '{}' is not an option letter", number, q.answer));
+                    }
+                    Some(index) if index >= q.options.len() => {
+                        return Err(format!("question {}: answer '{}' but only {} options",
+                            number, q.answer, q.options.len()));
+                    }
+                    Some(_) => {}
+                }
+            }
+            QuestionKind::TrueFalse => {
+                if parse_true_false(&q.answer.trim().to_lowercase()).is_none() {
+                    return Err(format!("question {}: answer '{}' is not true or false", number, q.answer));
+                }
+            }
+            QuestionKind::FreeText => {
+                if q.answer.trim().is_empty() {
+                    return Err(format!("question {}: answer is empty", number));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// The next answer from the batch file, or a line typed at the prompt.
+// `None` means the batch ran out or the time was up.
+fn read_answer(q: &Question, source: &mut AnswerSource) -> Result<Option<String>, Interrupted> {
+    if INTERRUPTED.load(Ordering::Relaxed) {
+        return Err(Interrupted);
+    }
+    match source {
+        AnswerSource::Batch(answers) => Ok(answers.next().map(|a| a.trim().to_lowercase())),
+        AnswerSource::Typed { input, closed, timeout } => {
+            println!("{}", q.prompt());
+            io::stdout().flush().unwrap();
+
+            // Once stdin is closed every answer is empty, as read_line gives
+            if *closed {
+                return Ok(Some(String::new()));
+            }
+            let event = match timeout {
+                Some(timeout) => match input.recv_timeout(*timeout) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => {
+                        println!("Time's up!");
+                        return Ok(None);
+                    }
+                    Err(RecvTimeoutError::Disconnected) => Input::Eof,
+                },
+                None => input.recv().unwrap_or(Input::Eof),
+            };
+            match event {
+                Input::Line(line) => Ok(Some(line.trim().to_lowercase())),
+                Input::Eof => {
+                    *closed = true;
+                    Ok(Some(String::new()))
+                }
+                Input::Interrupted => Err(Interrupted),
+            }
+        }
+    }
+}
+
+fn load_questions(path: &Path) -> Result<Vec<Question>, Box<dyn Error>> {
+    let data = fs::read_to_string(path)?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Ok(serde_json::from_str(&data)?),
+        Some("yaml") | Some("yml") => Ok(serde_yaml::from_str(&data)?),
+        Some("csv") => {
+            let mut reader = csv::ReaderBuilder::new()
+                .flexible(true)
+                .trim(csv::Trim::Headers)
+                .from_reader(data.as_bytes());
+            let mut questions = Vec::new();
+            for row in reader.deserialize::<CsvQuestion>() {
+                let question = Question::try_from(row?).map_err(|e| format!("question {}: {}", questions.len() + 1, e))?;
+                questions.push(question);
+            }
+            Ok(questions)
+        }
+        _ => Err("unsupported file extension (expected .json, .yaml, .yml or .csv)".into()),
+    }
+}
+
+fn save_questions(path: &Path, questions: &[Question]) -> Result<(), Box<dyn Error>> {
+    let data = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::to_string_pretty(questions)?,
+        Some("yaml") | Some("yml") => serde_yaml::to_string(questions)?,
+        _ => return Err("samples can only be written as .json, .yaml or .yml".into()),
+    };
+    fs::write(path, data)?;
+    Ok(())
+}
+
+// A few questions showing off each question kind and the optional fields.
+fn sample_questions() -> Vec<Question> {
+    vec![
+        Question {
+            question: "What is the capital of France?".to_string(),
+            kind: QuestionKind::MultipleChoice,
+            options: ["a) Berlin", "b) Paris", "c) Rome", "d) Madrid"].map(String::from).to_vec(),
+            answer: "b".to_string(),
+            accepted: Vec::new(),
+            explanation: Some("Paris has been the capital of France since 987.".to_string()),
+            category: Some("geography".to_string()),
+            difficulty: Some("easy".to_string()),
+            points: None,
+        },
+        Question {
+            question: "Rust has a garbage collector.".to_string(),
+            kind: QuestionKind::TrueFalse,
+            options: Vec::new(),
+            answer: "false".to_string(),
+            accepted: Vec::new(),
+            explanation: Some("Rust frees memory through ownership, not a garbage collector.".to_string()),
+            category: Some("programming".to_string()),
+            difficulty: Some("easy".to_string()),
+            points: None,
+        },
+        Question {
+            question: "Which keyword declares a variable in Rust?".to_string(),
+            kind: QuestionKind::FreeText,
+            options: Vec::new(),
+            answer: "let".to_string(),
+            accepted: vec!["let mut".to_string()],
+            explanation: None,
+            category: Some("programming".to_string()),
+            difficulty: Some("medium".to_string()),
+            points: Some(2),
+        },
+        Question {
+            question: "What is the largest planet in the solar system?".to_string(),
+            kind: QuestionKind::MultipleChoice,
+            options: ["a) Saturn", "b) Earth", "c) Jupiter", "d) Neptune"].map(String::from).to_vec(),
+            answer: "c".to_string(),
+            accepted: Vec::new(),
+            explanation: None,
+            category: Some("science".to_string()),
+            difficulty: Some("medium".to_string()),
+            points: None,
+        },
+    ]
+}
+
+fn load_answers(path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let data = fs::read_to_string(path)?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Ok(serde_json::from_str(&data)?),
+        _ => Ok(data.lines().map(str::to_string).collect()),
+    }
+}
+
+fn percentage(score: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        score as f64 / total as f64 * 100.0
+    }
+}
+
+fn letter_grade(pct: f64, thresholds: &[f64]) -> &'static str {
+    ["A", "B", "C", "D"]
+        .into_iter()
+        .zip(thresholds)
+        .find(|&(_, &min)| pct >= min)
+        .map_or("F", |(grade, _)| grade)
+}
+
+fn colorize(text: &str, good: bool) -> String {
+    if good {
+        text.if_supports_color(Stream::Stdout, |s| s.green()).to_string()
+    } else {
+        text.if_supports_color(Stream::Stdout, |s| s.red()).to_string()
+    }
+}
+
+fn print_questions(questions: &[Question]) {
     for (i, q) in questions.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
         println!("{}. {}", i + 1, q.question);
-        for opt in &q.options {
-            println!("{}", opt);
+        match q.kind {
+            QuestionKind::MultipleChoice => {
+                for opt in &q.options {
+                    println!("   {}", opt);
+                }
+            }
+            QuestionKind::TrueFalse => println!("   True / False"),
+            QuestionKind::FreeText => println!("   ____________________"),
+        }
+    }
+}
+
+fn print_key(questions: &[Question]) {
+    println!("Answer key:");
+    for (i, q) in questions.iter().enumerate() {
+        println!("{}. {}", i + 1, q.key_answer());
+    }
+}
+
+fn list_categories(questions: &[Question]) {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for q in questions {
+        if let Some(category) = &q.category {
+            *counts.entry(category).or_insert(0) += 1;
         }
+    }
+
+    if counts.is_empty() {
+        println!("No categories defined.");
+        return;
+    }
 
-        println!("Your answer (a / b / c / d):");
-        io::stdout().flush().unwrap();
+    println!("Categories:");
+    for (category, count) in counts {
+        println!("  {} ({} questions)", category, count);
+    }
+}
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).expect("Failed to read");
-        let user_answer = input.trim().to_lowercase();
+fn print_high_scores(quiz: &str) {
+    let mut scores: Vec<ScoreEntry> = load_scores()
+        .into_iter()
+        .filter(|s| s.quiz == quiz)
+        .collect();
 
-        if user_answer == q.answer {
-            println!("✅ Correct!\n");
-            score += 1;
-        } else {
-            println!("❌ Wrong! Correct answer: {}\n", q.answer);
+    if scores.is_empty() {
+        println!("No scores recorded for {} yet.", quiz);
+        return;
+    }
+
+    scores.sort_by(|a, b| b.percentage.total_cmp(&a.percentage));
+
+    println!("High scores for {}:", quiz);
+    for (i, s) in scores.iter().enumerate() {
+        println!(
+            "{}. {}/{} ({:.1}%) - {}",
+            i + 1,
+            s.score,
+            s.total,
+            s.percentage,
+            s.timestamp
+        );
+    }
+}
+
+fn save_result(path: &Path, result: &QuizResult) -> Result<(), Box<dyn Error>> {
+    fs::write(path, serde_json::to_string_pretty(result)?)?;
+    Ok(())
+}
+
+fn load_scores() -> Vec<ScoreEntry> {
+    if Path::new(SCORES_PATH).exists() {
+        let data = fs::read_to_string(SCORES_PATH).expect("Unable to read scores file");
+        serde_json::from_str(&data).unwrap_or_else(|_| vec![])
+    } else {
+        vec![]
+    }
+}
+
+fn save_scores(scores: &[ScoreEntry]) {
+    let data = serde_json::to_string_pretty(scores).expect("Unable to serialize scores");
+    fs::write(SCORES_PATH, data).expect("Unable to write scores file");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multiple_choice(question: &str, options: &[&str], answer: &str) -> Question {
+        Question {
+            question: question.to_string(),
+            kind: QuestionKind::MultipleChoice,
+            options: options.iter().map(|o| o.to_string()).collect(),
+            answer: answer.to_string(),
+            accepted: Vec::new(),
+            explanation: None,
+            category: None,
+            difficulty: None,
+            points: None,
+        }
+    }
+
+    fn bank() -> Vec<Question> {
+        (0..10)
+            .map(|i| multiple_choice(&format!("Q{}", i), &["a) one", "b) two", "c) three", "d) four"], "c"))
+            .collect()
+    }
+
+    fn order(questions: &[Question]) -> Vec<String> {
+        questions.iter().map(|q| format!("{} {}", q.question, q.options.join(" "))).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_order() {
+        let (mut first, mut second, mut other) = (bank(), bank(), bank());
+        shuffle_questions(&mut first, 42);
+        shuffle_questions(&mut second, 42);
+        shuffle_questions(&mut other, 43);
+        assert_eq!(order(&first), order(&second));
+        assert_ne!(order(&first), order(&other));
+    }
+
+    #[test]
+    fn shuffled_answer_follows_its_option() {
+        let mut questions = bank();
+        shuffle_questions(&mut questions, 7);
+        for q in &questions {
+            let index = letter_index(&q.answer).unwrap();
+            assert_eq!(q.options[index], format!("{}) three", q.answer));
         }
     }
 
-    println!("Quiz Complete! Your Score: {}/{}", score, questions.len());
-}
\ No newline at end of file
+    // Each test names its own scratch directory, since they run in parallel
+    fn from_csv(name: &str, data: &str) -> Result<Vec<Question>, String> {
+        let dir = std::env::temp_dir().join(format!("quiz-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("questions.csv");
+        fs::write(&path, data).unwrap();
+        let questions = load_questions(&path).map_err(|e| e.to_string());
+        fs::remove_dir_all(&dir).unwrap();
+        questions
+    }
+
+    #[test]
+    fn sample_round_trips() {
+        let dir = std::env::temp_dir().join(format!("quiz-sample-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let sample = sample_questions();
+        for kind in [QuestionKind::MultipleChoice, QuestionKind::TrueFalse, QuestionKind::FreeText] {
+            assert!(sample.iter().any(|q| q.kind == kind), "no {:?} question in the sample", kind);
+        }
+
+        for name in ["questions.json", "questions.yaml"] {
+            let path = dir.join(name);
+            save_questions(&path, &sample).unwrap();
+            let mut loaded = load_questions(&path).unwrap();
+            check_questions(&mut loaded).unwrap();
+            assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&sample).unwrap(), "{}", name);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    const HEADER: &str = "question,option_a,option_b,option_c,option_d,answer\n";
+
+    #[test]
+    fn csv_answer_follows_its_option_past_a_blank() {
+        let questions = from_csv("blank", &format!("{}Pick,x,,right,z,c\n", HEADER)).unwrap();
+        let q = &questions[0];
+        assert_eq!(q.options, ["a) x", "b) right", "c) z"]);
+        assert_eq!(q.answer, "b");
+        assert!(q.is_correct("b"));
+    }
+
+    #[test]
+    fn csv_two_option_rows() {
+        let questions = from_csv("two", &format!("{}Sky is blue?,True,False,,,A\n", HEADER)).unwrap();
+        assert_eq!(questions[0].options, ["a) True", "b) False"]);
+        assert_eq!(questions[0].answer, "a");
+    }
+
+    #[test]
+    fn csv_answer_on_a_blank_option_is_an_error() {
+        let err = from_csv("error", &format!("{}Ok,x,y,z,w,a\nPick,x,,right,z,b\n", HEADER)).unwrap_err();
+        assert_eq!(err, "question 2: answer 'b' is a blank option");
+    }
+}
@@ -0,0 +1,82 @@
+//! End-to-end checks that run the built binary in batch mode.
+
+use std::{fs, process::Command};
+use tempfile::TempDir;
+
+const QUESTIONS: &str = r#"[
+  {"question": "2+2?", "options": ["a) 3", "b) 4"], "answer": "b"},
+  {"question": "Sky blue?", "kind": "true_false", "answer": "true"},
+  {"question": "Capital of Italy?", "kind": "free_text", "answer": "Rome"},
+  {"question": "Largest planet?", "options": ["a) Mars", "b) Jupiter"], "answer": "b", "points": 3}
+]"#;
+
+/// Runs the quiz in a scratch directory, which is also where it keeps its
+/// scores and looks for its config file.
+fn run_quiz(dir: &TempDir, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_quize-app"))
+        .args(args)
+        .current_dir(dir.path())
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("NO_COLOR", "1")
+        .env_remove("QUIZ_DIR")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn output_records_each_outcome() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("q.json"), QUESTIONS).unwrap();
+    // Right, wrong, skipped, and then the answers run out
+    fs::write(dir.path().join("answers.txt"), "b\nfalse\nskip\n").unwrap();
+
+    run_quiz(&dir, &["-f", "q.json", "--answers", "answers.txt", "--skip", "-o", "result.json"]);
+    let result: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.path().join("result.json")).unwrap()).unwrap();
+
+    assert_eq!(result["quiz"], "q.json");
+    assert_eq!(result["score"], 1);
+    assert_eq!(result["total"], 6);
+    assert!((result["percentage"].as_f64().unwrap() - 100.0 / 6.0).abs() < 1e-9);
+    assert!(result["seconds"].as_f64().unwrap() >= 0.0);
+    assert!(result["timestamp"].is_string());
+
+    let outcomes: Vec<(&str, &serde_json::Value, u64)> = result["questions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|q| (q["outcome"].as_str().unwrap(), &q["given"], q["points"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(
+        outcomes,
+        [
+            ("correct", &serde_json::json!("b"), 1),
+            ("incorrect", &serde_json::json!("false"), 0),
+            ("skipped", &serde_json::Value::Null, 0),
+            ("unanswered", &serde_json::Value::Null, 0),
+        ]
+    );
+}
+
+#[test]
+fn print_key_lists_each_answer() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("q.json"), QUESTIONS).unwrap();
+    let out = run_quiz(&dir, &["-f", "q.json", "--print-key"]);
+    assert_eq!(out, "Answer key:\n1. b) 4\n2. True\n3. Rome\n4. b) Jupiter\n");
+    assert!(!dir.path().join("scores.json").exists());
+}
+
+#[test]
+fn print_key_follows_a_seeded_shuffle() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("q.json"), QUESTIONS).unwrap();
+    let sheet = run_quiz(&dir, &["-f", "q.json", "--seed", "3", "--print-questions", "--print-key"]);
+    // Each multiple-choice key entry names the option the sheet shows under that letter
+    let (questions, key) = sheet.split_once("Answer key:\n").unwrap();
+    for line in key.lines().filter(|line| line.contains(") ")) {
+        let (_, option) = line.split_once(". ").unwrap();
+        assert!(questions.contains(&format!("   {}\n", option)), "{} not on the sheet:\n{}", option, sheet);
+    }
+}
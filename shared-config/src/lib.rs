@@ -0,0 +1,50 @@
+//! The settings file shared by the workout tools: one `config.toml` in the
+//! platform config directory (e.g. `~/.config/rust-workouts/config.toml` on
+//! Linux), with a table for each tool:
+//!
+//! ```toml
+//! [csv_tool]
+//! delimiter = ";"
+//!
+//! [todo]
+//! file = "/home/me/notes/tasks.json"
+//!
+//! [quiz]
+//! dir = "/home/me/quizzes"
+//! no_color = true
+//! ```
+//!
+//! Each tool reads only its own table. A setting from the file overrides the
+//! tool's built-in default, and is in turn overridden by the matching
+//! environment variable and command-line flag.
+
+use serde::de::DeserializeOwned;
+use std::{error::Error, fs, io::ErrorKind, path::PathBuf};
+
+/// Where the settings file is looked for, if the platform has a config directory.
+pub fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("rust-workouts").join("config.toml"))
+}
+
+/// Reads the `section` table of the settings file. A missing file or table
+/// gives `T::default()`; a file that can't be parsed, or a table that doesn't
+/// fit `T`, is an error naming the file.
+pub fn load<T: DeserializeOwned + Default>(section: &str) -> Result<T, Box<dyn Error>> {
+    let Some(path) = path() else {
+        return Ok(T::default());
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(format!("Can't read {}: {}", path.display(), e).into()),
+    };
+    let mut table: toml::Table = text
+        .parse()
+        .map_err(|e| format!("Can't parse {}: {}", path.display(), e))?;
+    match table.remove(section) {
+        Some(value) => value
+            .try_into()
+            .map_err(|e| format!("Invalid [{}] table in {}: {}", section, path.display(), e).into()),
+        None => Ok(T::default()),
+    }
+}
@@ -1,68 +1,1191 @@
-use clap::{Parser, Subcommand};
+use chrono::{Days, Local, Months, NaiveDate};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use fs2::FileExt;
+use owo_colors::{OwoColorize, Stream};
 use serde::{Serialize, Deserialize};
-use std::{fs, path::Path};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{HashMap, HashSet},
+    fs::{self, OpenOptions},
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::OnceLock,
+};
 
 #[derive(Parser)]
 #[command(name = "Todo CLI")]
 #[command(about = "A simple CLI To-Do list app")]
-
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Disable colored output (also honors the NO_COLOR environment variable)
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Task file; the archive, undo and lock files are kept beside it [default: tasks.json]
+    // Named apart from `import`'s `file`, which a global arg's id would clash with
+    #[arg(long = "file", global = true, env = "TODO_FILE", value_name = "PATH")]
+    tasks_file: Option<PathBuf>,
+}
+
+/// The `[todo]` table of the shared config file. Flags and environment
+/// variables override these.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    file: Option<PathBuf>,
+    no_color: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    Add { task: String },
-    List,
-    Remove { index: usize },
+    Add {
+        task: String,
+        #[arg(short, long, value_enum)]
+        priority: Option<Priority>,
+        /// Due date (YYYY-MM-DD)
+        #[arg(short, long)]
+        due: Option<NaiveDate>,
+        /// Tag the task (repeatable)
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+        /// Repeat the task: completing it moves the due date on instead
+        #[arg(short, long, value_enum)]
+        recur: Option<Recur>,
+        #[command(flatten)]
+        notes: NotesArgs,
+    },
+    /// Change a task's description or notes
+    #[command(group(clap::ArgGroup::new("change").required(true).multiple(true).args(["description", "notes", "notes_file"])))]
+    Edit {
+        index: usize,
+        /// New description
+        description: Option<String>,
+        #[command(flatten)]
+        notes: NotesArgs,
+    },
+    /// Show one task in full, notes included
+    Show { index: usize },
+    List {
+        /// Sort the displayed tasks without changing their stored order
+        #[arg(short, long, value_enum)]
+        sort: Option<SortKey>,
+        /// Reverse the sort order (tasks missing the key still come last)
+        #[arg(short, long, requires = "sort")]
+        reverse: bool,
+        /// Group tasks under their tags
+        #[arg(short, long)]
+        grouped: bool,
+        /// Only show tasks with this tag
+        #[arg(short, long)]
+        tag: Option<String>,
+        /// Only show tasks due today
+        #[arg(long, conflicts_with = "week")]
+        today: bool,
+        /// Only show tasks due in the next seven days, today included
+        #[arg(long)]
+        week: bool,
+        /// Print one undecorated tab-separated line per task for scripts:
+        /// id, status, priority, due, description ("-" for no value)
+        #[arg(long, conflicts_with = "grouped")]
+        porcelain: bool,
+    },
+    /// Remove a task, asking for confirmation when run from a terminal
+    Remove {
+        index: usize,
+        /// Remove without asking
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Mark a task as completed
+    Done { index: usize },
+    /// Mark a completed task as pending again
+    Undone { index: usize },
+    /// Move a task to a new position in the list
+    #[command(group(clap::ArgGroup::new("target").required(true).args(["to", "up", "down"])))]
+    Move {
+        index: usize,
+        to: Option<usize>,
+        /// Move the task one position up
+        #[arg(long)]
+        up: bool,
+        /// Move the task one position down
+        #[arg(long)]
+        down: bool,
+    },
+    /// Add every non-empty line of a text file as a task ('#' starts a comment)
+    Import { file: PathBuf },
+    /// Write the tasks to a CSV file
+    Export {
+        file: PathBuf,
+        /// Add to the end of the file instead of replacing it; the header
+        /// is only written when the file is new or empty
+        #[arg(long)]
+        append: bool,
+    },
+    /// Start an interactive session (add ..., list, done N, quit)
+    Interactive,
+    /// Move completed tasks into the archive
+    Archive,
+    /// List archived tasks
+    ListArchive,
+    /// List completed tasks, archived ones included, by completion date
+    History {
+        /// Only tasks completed on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<NaiveDate>,
+    },
+    /// Show an overview of the task list
+    Stats {
+        /// Also list how many tasks were completed on each of the last 7 days
+        #[arg(long)]
+        daily: bool,
+    },
+    /// Revert the last command that changed the task list or archive
+    Undo,
+    /// Print a shell completion script (bash, elvish, fish, powershell, zsh)
+    #[command(hide = true)]
+    Completions { shell: Shell },
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Args)]
+struct NotesArgs {
+    /// Longer notes shown by `show` (an empty string removes them)
+    #[arg(short, long, conflicts_with = "notes_file")]
+    notes: Option<String>,
+    /// Read the notes from a file
+    #[arg(long, value_name = "PATH")]
+    notes_file: Option<PathBuf>,
+}
+
+impl NotesArgs {
+    // None when neither flag was given; Some(None) when the notes are cleared
+    fn read(self) -> Result<Option<Option<String>>, String> {
+        let text = match (self.notes, self.notes_file) {
+            (Some(notes), _) => notes,
+            (None, Some(path)) => {
+                fs::read_to_string(&path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?
+            }
+            (None, None) => return Ok(None),
+        };
+        let text = text.trim_end();
+        Ok(Some((!text.is_empty()).then(|| text.to_string())))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+impl Priority {
+    fn label(self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum Recur {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Recur {
+    fn label(self) -> &'static str {
+        match self {
+            Recur::Daily => "daily",
+            Recur::Weekly => "weekly",
+            Recur::Monthly => "monthly",
+        }
+    }
+
+    // Monthly steps keep the day where possible, clamping to the month's end.
+    fn advance(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Recur::Daily => date + Days::new(1),
+            Recur::Weekly => date + Days::new(7),
+            Recur::Monthly => date + Months::new(1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SortKey {
+    Priority,
+    Due,
+    Created,
+    Alpha,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 struct Task {
     description: String,
+    #[serde(default)]
+    completed: bool,
+    #[serde(default)]
+    priority: Option<Priority>,
+    #[serde(default)]
+    due: Option<NaiveDate>,
+    #[serde(default)]
+    created: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    recur: Option<Recur>,
+    #[serde(default)]
+    notes: Option<String>,
+    #[serde(default)]
+    completed_at: Option<NaiveDate>,
+    /// The days a recurring task was done on; it never stays completed, so
+    /// `completed_at` can't hold them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    completions: Vec<NaiveDate>,
 }
 
 const FILE_PATH: &str = "tasks.json";
+const ARCHIVE_NAME: &str = "archive.json";
+const UNDO_NAME: &str = "undo.json";
+const LOCK_NAME: &str = "tasks.lock";
+
+/// Set once at startup by `--file`, `TODO_FILE` or the config file.
+static TASKS_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+fn tasks_file() -> &'static Path {
+    TASKS_FILE.get_or_init(|| PathBuf::from(FILE_PATH))
+}
+
+// The archive, undo snapshot and lock live in the task file's directory
+fn data_file(name: &str) -> PathBuf {
+    tasks_file().with_file_name(name)
+}
+
+/// Both lists as they were before the last change, for `undo`.
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    tasks: Vec<Task>,
+    archive: Vec<Task>,
+}
+
+impl Commands {
+    // Spelled out per variant so a new command has to decide whether it
+    // needs an undo snapshot.
+    fn mutates(&self) -> bool {
+        match self {
+            Commands::Add { .. }
+            | Commands::Edit { .. }
+            | Commands::Import { .. }
+            | Commands::Remove { .. }
+            | Commands::Done { .. }
+            | Commands::Undone { .. }
+            | Commands::Move { .. }
+            | Commands::Archive => true,
+            Commands::List { .. }
+            | Commands::Show { .. }
+            | Commands::Interactive
+            | Commands::ListArchive
+            | Commands::Export { .. }
+            | Commands::History { .. }
+            | Commands::Stats { .. }
+            | Commands::Undo
+            | Commands::Completions { .. } => false,
+        }
+    }
+}
 
 fn main() {
     let cli = Cli::parse();
-    let mut tasks: Vec<Task> = load_tasks();
+    let config: Config = shared_config::load("todo").unwrap_or_else(|e| {
+        print_error(&e.to_string());
+        process::exit(1);
+    });
+
+    if cli.no_color || config.no_color {
+        owo_colors::set_override(false);
+    }
+    if let Some(file) = cli.tasks_file.or(config.file) {
+        TASKS_FILE.get_or_init(|| file);
+    }
 
-    
     match cli.command {
-        Commands::Add { task } => {
-            tasks.push(Task { description: task });
-            save_tasks(&tasks);
-            println!("Task added.");
+        Commands::Interactive => run_interactive(),
+        command => with_lock(|| run_command(command, &mut load_tasks())),
+    }
+}
+
+// Holds an exclusive lock on LOCK_NAME while `f` runs, so two invocations
+// can't interleave their load-change-save and lose each other's changes.
+// The lock lives on its own file because saving renames over tasks.json.
+// It is released when the file closes, including on a panic.
+fn with_lock<T>(f: impl FnOnce() -> T) -> T {
+    let lock = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(data_file(LOCK_NAME))
+        .expect("Unable to open lock file");
+    lock.lock_exclusive().expect("Unable to lock task file");
+    let result = f();
+    let _ = FileExt::unlock(&lock);
+    result
+}
+
+// Runs one command against the loaded tasks, going through `with_snapshot`
+// for any command that can change them.
+fn run_command(command: Commands, tasks: &mut Vec<Task>) {
+    if command.mutates() {
+        with_snapshot(tasks, |tasks| execute(command, tasks));
+    } else {
+        execute(command, tasks);
+    }
+}
+
+// Runs `change`, then records the lists as they were before it in the undo
+// file. Commands that end up changing nothing leave the previous snapshot.
+fn with_snapshot(tasks: &mut Vec<Task>, change: impl FnOnce(&mut Vec<Task>)) {
+    let before = Snapshot { tasks: tasks.clone(), archive: load_archive() };
+    change(tasks);
+    if before.tasks != *tasks || before.archive != load_archive() {
+        let data = serde_json::to_string_pretty(&before).expect("Unable to serialize");
+        write_atomic(&data_file(UNDO_NAME), &data);
+    }
+}
+
+// Carries out one command, saving after any change.
+fn execute(command: Commands, tasks: &mut Vec<Task>) {
+    match command {
+        Commands::Add { task, priority, due, tags, recur, notes } => {
+            let notes = match notes.read() {
+                Ok(notes) => notes.flatten(),
+                Err(e) => {
+                    print_error(&e);
+                    return;
+                }
+            };
+            let mut task = new_task(task, priority, due, tags);
+            task.recur = recur;
+            task.notes = notes;
+            tasks.push(task);
+            save_tasks(tasks);
+            print_success("Task added.");
+        }
+        Commands::Edit { index, description, notes } => {
+            let notes = match notes.read() {
+                Ok(notes) => notes,
+                Err(e) => {
+                    print_error(&e);
+                    return;
+                }
+            };
+            let Some(task) = tasks.get_mut(index) else {
+                print_error("Invalid index.");
+                return;
+            };
+            if let Some(description) = description {
+                task.description = description;
+            }
+            if let Some(notes) = notes {
+                task.notes = notes;
+            }
+            save_tasks(tasks);
+            print_success("Task updated.");
         }
-        Commands::List => {
-            for (i, task) in tasks.iter().enumerate() {
-                println!("{}: {}", i, task.description);
+        Commands::Show { index } => match tasks.get(index) {
+            Some(task) => print_task_details(index, task),
+            None => print_error("Invalid index."),
+        },
+        Commands::Import { file } => {
+            let data = match fs::read_to_string(&file) {
+                Ok(data) => data,
+                Err(e) => {
+                    print_error(&format!("Unable to read {}: {}", file.display(), e));
+                    return;
+                }
+            };
+            let before = tasks.len();
+            tasks.extend(
+                data.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(|line| new_task(line.to_string(), None, None, Vec::new())),
+            );
+            let count = tasks.len() - before;
+            // One save for the whole file rather than one per line
+            if count > 0 {
+                save_tasks(tasks);
+            }
+            print_success(&format!("Imported {} tasks.", count));
+        }
+        Commands::Export { file, append } => match export_tasks(tasks, &file, append) {
+            Ok(()) => print_success(&format!("Exported {} tasks.", tasks.len())),
+            Err(e) => print_error(&format!("Unable to write {}: {}", file.display(), e)),
+        },
+        Commands::List { sort, reverse, grouped, tag, today, week, porcelain } => {
+            let window = if today { Some(1) } else if week { Some(7) } else { None };
+            let reference = Local::now().date_naive();
+            let mut listed: Vec<(usize, &Task)> = tasks
+                .iter()
+                .enumerate()
+                .filter(|(_, task)| tag.as_ref().is_none_or(|tag| task.tags.contains(tag)))
+                .filter(|(_, task)| window.is_none_or(|days| due_within(task, reference, days)))
+                .collect();
+            if let Some(key) = sort {
+                listed.sort_by(|a, b| compare_tasks(a.1, b.1, key, reverse));
+            }
+            if porcelain {
+                print_porcelain(&listed);
+            } else if grouped {
+                print_grouped_tasks(&listed);
+            } else {
+                print_tasks(&listed);
             }
         }
-        Commands::Remove { index } => {
+        Commands::Remove { index, force } => {
             if index < tasks.len() {
+                let prompt = format!("Remove \"{}\"?", tasks[index].description);
+                if !force && !confirm(&prompt) {
+                    println!("Nothing removed.");
+                    return;
+                }
                 tasks.remove(index);
-                save_tasks(&tasks);
-                println!("Task removed.");
+                save_tasks(tasks);
+                print_success("Task removed.");
+            } else {
+                print_error("Invalid index.");
+            }
+        }
+        Commands::Move { index, to, up, down } => {
+            if index < tasks.len() {
+                let target = if up {
+                    index.saturating_sub(1)
+                } else if down {
+                    index + 1
+                } else {
+                    to.unwrap_or(index)
+                };
+                let new_index = move_task(tasks, index, target);
+                save_tasks(tasks);
+                print_success(&format!("Task moved to {}.", new_index));
+            } else {
+                print_error("Invalid index.");
+            }
+        }
+        Commands::Done { index } => {
+            if let Some(task) = tasks.get_mut(index) {
+                let next = complete(task, Local::now().date_naive());
+                save_tasks(tasks);
+                match next {
+                    Some(next) => print_success(&format!("Task completed; next due {}.", next)),
+                    None => print_success("Task completed."),
+                }
             } else {
-                println!("Invalid index.");
+                print_error("Invalid index.");
+            }
+        }
+        Commands::Undone { index } => match tasks.get_mut(index) {
+            Some(task) if task.completed => {
+                task.completed = false;
+                task.completed_at = None;
+                save_tasks(tasks);
+                print_success("Task marked as pending.");
+            }
+            Some(_) => print_error("Task isn't completed."),
+            None => print_error("Invalid index."),
+        },
+        Commands::Archive => {
+            let (done, pending): (Vec<Task>, Vec<Task>) = std::mem::take(tasks).into_iter().partition(|t| t.completed);
+            let count = done.len();
+            if count > 0 {
+                let mut archive = load_archive();
+                archive.extend(done);
+                // Archive first so a failure can't drop completed tasks
+                save_archive(&archive);
+                save_tasks(&pending);
+            }
+            *tasks = pending;
+            print_success(&format!("Archived {} tasks.", count));
+        }
+        Commands::ListArchive => {
+            let archive = load_archive();
+            print_tasks(&archive.iter().enumerate().collect::<Vec<_>>());
+        }
+        Commands::History { since } => print_history(tasks, &load_archive(), since),
+        Commands::Stats { daily } => print_stats(tasks, &load_archive(), daily),
+        Commands::Undo => undo(tasks),
+        Commands::Interactive => print_error("Already in interactive mode."),
+        Commands::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "todo", &mut io::stdout());
+        }
+    }
+}
+
+fn new_task(description: String, priority: Option<Priority>, due: Option<NaiveDate>, tags: Vec<String>) -> Task {
+    Task {
+        description,
+        completed: false,
+        priority,
+        due,
+        created: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        tags,
+        recur: None,
+        notes: None,
+        completed_at: None,
+        completions: Vec::new(),
+    }
+}
+
+// Marks a task done today. A recurring task stays pending and comes due
+// again, so its next due date is returned.
+fn complete(task: &mut Task, today: NaiveDate) -> Option<NaiveDate> {
+    if let Some(recur) = task.recur {
+        let next = recur.advance(task.due.unwrap_or(today));
+        task.due = Some(next);
+        task.completions.push(today);
+        Some(next)
+    } else {
+        task.completed = true;
+        task.completed_at = Some(today);
+        None
+    }
+}
+
+// Each command reloads the tasks under the lock, so changes made by other
+// invocations in the meantime are picked up rather than overwritten.
+fn run_interactive() {
+    println!("Interactive mode. Type 'help' for commands, 'quit' to exit.");
+
+    loop {
+        print!("todo> ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        // Ctrl-D (EOF) ends the session like `quit`
+        if io::stdin().read_line(&mut line).expect("Failed to read") == 0 {
+            println!();
+            break;
+        }
+
+        let words = split_line(&line);
+        match words.first().map(String::as_str) {
+            None => continue,
+            Some("quit") | Some("exit") => break,
+            _ => {}
+        }
+
+        match Cli::try_parse_from(std::iter::once("todo".to_string()).chain(words)) {
+            Ok(cli) => with_lock(|| run_command(cli.command, &mut load_tasks())),
+            Err(e) => {
+                let _ = e.print();
             }
         }
     }
 }
 
+// Splits a line on whitespace, keeping "quoted" or 'quoted' text together.
+fn split_line(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    words
+}
+
+// Reinserts the task at `index` at `to`, clamped to the end of the list.
+fn move_task(tasks: &mut Vec<Task>, index: usize, to: usize) -> usize {
+    let task = tasks.remove(index);
+    let to = to.min(tasks.len());
+    tasks.insert(to, task);
+    to
+}
+
+// Whether `task` is due in the `days` days starting at `today`; tasks without
+// a due date never are.
+fn due_within(task: &Task, today: NaiveDate, days: u64) -> bool {
+    task.due.is_some_and(|due| due >= today && due < today + Days::new(days))
+}
+
+// Orders tasks by `key`; tasks without a value for the key always sort last.
+fn compare_tasks(a: &Task, b: &Task, key: SortKey, reverse: bool) -> Ordering {
+    fn missing_last<T: Ord>(a: Option<T>, b: Option<T>, reverse: bool) -> Ordering {
+        match (a, b) {
+            (Some(a), Some(b)) if reverse => b.cmp(&a),
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+
+    match key {
+        // Highest priority first
+        SortKey::Priority => missing_last(a.priority.map(Reverse), b.priority.map(Reverse), reverse),
+        SortKey::Due => missing_last(a.due, b.due, reverse),
+        SortKey::Created => missing_last(
+            Some(&a.created).filter(|c| !c.is_empty()),
+            Some(&b.created).filter(|c| !c.is_empty()),
+            reverse,
+        ),
+        SortKey::Alpha => missing_last(
+            Some(a.description.to_lowercase()),
+            Some(b.description.to_lowercase()),
+            reverse,
+        ),
+    }
+}
+
+fn print_tasks(tasks: &[(usize, &Task)]) {
+    for (i, task) in tasks {
+        let marker = if task.completed { "[x]" } else { "[ ]" };
+        let mut details = Vec::new();
+        if let Some(priority) = task.priority {
+            details.push(priority.label().to_string());
+        }
+        if let Some(due) = task.due {
+            details.push(format!("due {}", due));
+        }
+        if let Some(recur) = task.recur {
+            details.push(recur.label().to_string());
+        }
+        details.extend(task.tags.iter().map(|tag| format!("#{}", tag)));
+        let details = if details.is_empty() { String::new() } else { format!(" ({})", details.join(", ")) };
+        println!("{} {} {}{}", format!("{}:", i).if_supports_color(Stream::Stdout, |s| s.dimmed()), marker, task.description, details);
+    }
+}
+
+// The list line followed by what the list leaves out, with notes indented
+// under it.
+fn print_task_details(index: usize, task: &Task) {
+    print_tasks(&[(index, task)]);
+    if !task.created.is_empty() {
+        println!("    {}", format!("Created {}", task.created).if_supports_color(Stream::Stdout, |s| s.dimmed()));
+    }
+    if let Some(notes) = &task.notes {
+        println!();
+        for line in notes.lines() {
+            println!("    {}", line);
+        }
+    }
+}
+
+// The stable format behind `list --porcelain`: fields are never reordered and
+// tabs or newlines in descriptions become spaces so each task stays one line.
+fn print_porcelain(tasks: &[(usize, &Task)]) {
+    for (i, task) in tasks {
+        let status = if task.completed { "done" } else { "pending" };
+        let priority = task.priority.map_or("-", Priority::label);
+        let due = task.due.map_or("-".to_string(), |due| due.to_string());
+        let description = task.description.replace(['\t', '\n', '\r'], " ");
+        println!("{}\t{}\t{}\t{}\t{}", i, status, priority, due, description);
+    }
+}
+
+const EXPORT_HEADER: [&str; 7] = ["id", "status", "priority", "due", "tags", "completed_at", "description"];
+
+// One row per task, in the same column order as `list --porcelain` plus the
+// tags (';'-separated) and completion date. When appending to a file that
+// already has rows, its header is left as the only one.
+fn export_tasks(tasks: &[Task], path: &Path, append: bool) -> Result<(), csv::Error> {
+    let file = OpenOptions::new().write(true).create(true).append(append).truncate(!append).open(path)?;
+    let has_rows = append && file.metadata()?.len() > 0;
+    let mut writer = csv::Writer::from_writer(file);
+    if !has_rows {
+        writer.write_record(EXPORT_HEADER)?;
+    }
+    for (i, task) in tasks.iter().enumerate() {
+        let status = if task.completed { "done" } else { "pending" };
+        let priority = task.priority.map_or("", Priority::label);
+        let due = task.due.map(|d| d.to_string()).unwrap_or_default();
+        let completed_at = task.completed_at.map(|d| d.to_string()).unwrap_or_default();
+        let id = i.to_string();
+        let tags = task.tags.join(";");
+        writer.write_record([id.as_str(), status, priority, &due, &tags, &completed_at, &task.description])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// Prints tasks in sections by tag, with untagged tasks in a final "No tag"
+// section. A task with several tags appears under each of them.
+fn print_grouped_tasks(tasks: &[(usize, &Task)]) {
+    let mut groups: HashMap<&str, Vec<(usize, &Task)>> = HashMap::new();
+    let mut untagged = Vec::new();
+    for &(i, task) in tasks {
+        if task.tags.is_empty() {
+            untagged.push((i, task));
+        }
+        for tag in &task.tags {
+            let group = groups.entry(tag.as_str()).or_default();
+            // Guard against the same tag listed twice on one task
+            if group.last().is_none_or(|&(last, _)| last != i) {
+                group.push((i, task));
+            }
+        }
+    }
+
+    let mut names: Vec<&str> = groups.keys().copied().collect();
+    names.sort_unstable();
+    let sections = names
+        .into_iter()
+        .map(|name| (name, &groups[name]))
+        .chain((!untagged.is_empty()).then_some(("No tag", &untagged)));
+
+    for (n, (name, group)) in sections.enumerate() {
+        if n > 0 {
+            println!();
+        }
+        println!("{}", name.if_supports_color(Stream::Stdout, |s| s.bold()));
+        print_tasks(group);
+    }
+}
+
+// Every day a task was done on: its completion date, or each time a
+// recurring task was done. Tasks completed before completion dates were
+// recorded have none.
+fn completion_dates(task: &Task) -> impl Iterator<Item = NaiveDate> + '_ {
+    task.completed_at.filter(|_| task.completed).into_iter().chain(task.completions.iter().copied())
+}
+
+// Completions on or after `since`, oldest first. A recurring task appears
+// once for each time it was done.
+fn history<'a>(tasks: &'a [Task], archive: &'a [Task], since: Option<NaiveDate>) -> Vec<(NaiveDate, &'a Task)> {
+    let mut done: Vec<(NaiveDate, &Task)> = tasks
+        .iter()
+        .chain(archive)
+        .flat_map(|task| completion_dates(task).map(move |date| (date, task)))
+        .filter(|(date, _)| since.is_none_or(|since| *date >= since))
+        .collect();
+    // Stable, so tasks finished on the same day keep their list order
+    done.sort_by_key(|(date, _)| *date);
+    done
+}
+
+fn print_history(tasks: &[Task], archive: &[Task], since: Option<NaiveDate>) {
+    let done = history(tasks, archive, since);
+    if done.is_empty() {
+        println!("No completed tasks.");
+        return;
+    }
+    for (date, task) in done {
+        println!("{} {}", date.if_supports_color(Stream::Stdout, |s| s.dimmed()), task.description);
+    }
+}
+
+// Completions are counted from archived tasks too, so archiving doesn't
+// break a streak.
+fn print_stats(tasks: &[Task], archive: &[Task], daily: bool) {
+    let total = tasks.len();
+    let completed = tasks.iter().filter(|t| t.completed).count();
+    let percent = if total > 0 { completed as f64 / total as f64 * 100.0 } else { 0.0 };
+    let today = Local::now().date_naive();
+    let pending_due = || tasks.iter().filter(|t| !t.completed).filter_map(|t| t.due);
+
+    println!("Total tasks: {}", total);
+    println!("Completed: {} ({:.1}%)", completed, percent);
+    println!("Pending: {}", total - completed);
+
+    println!("By priority:");
+    for priority in [Priority::High, Priority::Medium, Priority::Low] {
+        let count = tasks.iter().filter(|t| t.priority == Some(priority)).count();
+        println!("  {}: {}", priority.label(), count);
+    }
+    println!("  none: {}", tasks.iter().filter(|t| t.priority.is_none()).count());
+
+    let overdue = pending_due().filter(|&due| due < today).count();
+    if overdue > 0 {
+        println!("{}", format!("Overdue: {}", overdue).if_supports_color(Stream::Stdout, |s| s.red()));
+    } else {
+        println!("Overdue: 0");
+    }
+    match pending_due().filter(|&due| due >= today).min() {
+        Some(due) => println!("Next due: {}", due),
+        None => println!("Next due: none"),
+    }
+
+    let completions: Vec<NaiveDate> = tasks.iter().chain(archive).flat_map(completion_dates).collect();
+    let days: HashSet<NaiveDate> = completions.iter().copied().collect();
+    let streak = streak(&days, today);
+    println!("Streak: {} day{}", streak, if streak == 1 { "" } else { "s" });
+
+    if daily {
+        println!("Last 7 days:");
+        for date in (0..7).rev().filter_map(|back| today.checked_sub_days(Days::new(back))) {
+            let count = completions.iter().filter(|&&done| done == date).count();
+            println!("  {} {}: {}", date, date.format("%a"), count);
+        }
+    }
+}
+
+// The number of consecutive days, ending today, with at least one
+// completion. A day without any breaks the streak, except that today
+// doesn't count against it until it's over: with nothing done yet today,
+// the streak runs back from yesterday. Dates are the local calendar dates
+// `done` records, so `today` must be the local date too.
+fn streak(days: &HashSet<NaiveDate>, today: NaiveDate) -> u32 {
+    let mut date = if days.contains(&today) { today } else { today - Days::new(1) };
+    let mut count = 0;
+    while days.contains(&date) {
+        count += 1;
+        date = date - Days::new(1);
+    }
+    count
+}
+
+// Asks a y/N question on stdin. Without a terminal to ask, the answer is yes
+// so scripts don't hang waiting for input.
+fn confirm(prompt: &str) -> bool {
+    if !io::stdin().is_terminal() {
+        return true;
+    }
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush().unwrap();
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).expect("Failed to read");
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+// Restores the last snapshot. It is consumed, so a second undo can't flip
+// back to the state that was just undone.
+fn undo(tasks: &mut Vec<Task>) {
+    let undo_file = data_file(UNDO_NAME);
+    let Ok(data) = fs::read_to_string(&undo_file) else {
+        print_error("Nothing to undo.");
+        return;
+    };
+    let snapshot: Snapshot = match serde_json::from_str(&data) {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            print_error(&format!("Unable to read {}: {}", undo_file.display(), e));
+            return;
+        }
+    };
+
+    save_archive(&snapshot.archive);
+    save_tasks(&snapshot.tasks);
+    fs::remove_file(&undo_file).expect("Unable to remove undo file");
+
+    let message = match snapshot.tasks.len().cmp(&tasks.len()) {
+        Ordering::Greater => format!("Restored {} removed task(s).", snapshot.tasks.len() - tasks.len()),
+        Ordering::Less => format!("Removed {} added task(s).", tasks.len() - snapshot.tasks.len()),
+        Ordering::Equal => "Restored the previous task list.".to_string(),
+    };
+    *tasks = snapshot.tasks;
+    print_success(&message);
+}
+
+fn print_success(message: &str) {
+    println!("{}", message.if_supports_color(Stream::Stdout, |s| s.green()));
+}
+
+fn print_error(message: &str) {
+    println!("{}", message.if_supports_color(Stream::Stdout, |s| s.red()));
+}
+
 fn load_tasks() -> Vec<Task> {
-    if Path::new(FILE_PATH).exists() {
-        let data = fs::read_to_string(FILE_PATH).expect("Unable to read file");
+    read_task_file(tasks_file())
+}
+
+fn save_tasks(tasks: &[Task]) {
+    write_task_file(tasks_file(), tasks);
+}
+
+fn load_archive() -> Vec<Task> {
+    read_task_file(&data_file(ARCHIVE_NAME))
+}
+
+fn save_archive(tasks: &[Task]) {
+    write_task_file(&data_file(ARCHIVE_NAME), tasks);
+}
+
+fn read_task_file(path: &Path) -> Vec<Task> {
+    if path.exists() {
+        let data = fs::read_to_string(path).expect("Unable to read file");
         serde_json::from_str(&data).unwrap_or_else(|_| vec![])
     } else {
         vec![]
     }
 }
 
-fn save_tasks(tasks: &Vec<Task>) {
+fn write_task_file(path: &Path, tasks: &[Task]) {
     let data = serde_json::to_string_pretty(tasks).expect("Unable to serialize");
-    fs::write(FILE_PATH, data).expect("Unable to write file");
+    write_atomic(path, &data);
 }
+
+// Writes to a temp file and renames it over `path`, so a crash mid-write
+// never leaves a truncated file behind.
+fn write_atomic(path: &Path, data: &str) {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    fs::write(&tmp_path, data).expect("Unable to write file");
+    fs::rename(&tmp_path, path).expect("Unable to replace file");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_flag_and_import_file_are_separate() {
+        let cli = Cli::try_parse_from(["todo", "--file", "mine.json", "import", "list.txt"]).unwrap();
+        assert_eq!(cli.tasks_file, Some(PathBuf::from("mine.json")));
+        match cli.command {
+            Commands::Import { file } => assert_eq!(file, PathBuf::from("list.txt")),
+            _ => panic!("expected import"),
+        }
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn named(names: &[&str]) -> Vec<Task> {
+        names.iter().map(|name| new_task(name.to_string(), None, None, Vec::new())).collect()
+    }
+
+    fn descriptions(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|task| task.description.as_str()).collect()
+    }
+
+    fn sorted(tasks: &[Task], key: SortKey, reverse: bool) -> Vec<&str> {
+        let mut sorted: Vec<&Task> = tasks.iter().collect();
+        sorted.sort_by(|a, b| compare_tasks(a, b, key, reverse));
+        sorted.into_iter().map(|task| task.description.as_str()).collect()
+    }
+
+    #[test]
+    fn sort_by_priority() {
+        let mut tasks = named(&["none", "low", "high", "medium"]);
+        tasks[1].priority = Some(Priority::Low);
+        tasks[2].priority = Some(Priority::High);
+        tasks[3].priority = Some(Priority::Medium);
+        assert_eq!(sorted(&tasks, SortKey::Priority, false), ["high", "medium", "low", "none"]);
+        assert_eq!(sorted(&tasks, SortKey::Priority, true), ["low", "medium", "high", "none"]);
+    }
+
+    #[test]
+    fn sort_by_due() {
+        let mut tasks = named(&["undated", "later", "sooner"]);
+        tasks[1].due = Some(date("2024-05-01"));
+        tasks[2].due = Some(date("2024-04-01"));
+        assert_eq!(sorted(&tasks, SortKey::Due, false), ["sooner", "later", "undated"]);
+        assert_eq!(sorted(&tasks, SortKey::Due, true), ["later", "sooner", "undated"]);
+    }
+
+    #[test]
+    fn sort_by_created() {
+        let mut tasks = named(&["old file", "newer", "older"]);
+        tasks[0].created = String::new();
+        tasks[1].created = "2024-03-02 09:00:00".into();
+        tasks[2].created = "2024-03-01 18:30:00".into();
+        assert_eq!(sorted(&tasks, SortKey::Created, false), ["older", "newer", "old file"]);
+        assert_eq!(sorted(&tasks, SortKey::Created, true), ["newer", "older", "old file"]);
+    }
+
+    #[test]
+    fn sort_alphabetically_ignoring_case() {
+        let tasks = named(&["banana", "Cherry", "apple"]);
+        assert_eq!(sorted(&tasks, SortKey::Alpha, false), ["apple", "banana", "Cherry"]);
+        assert_eq!(sorted(&tasks, SortKey::Alpha, true), ["Cherry", "banana", "apple"]);
+    }
+
+    #[test]
+    fn move_task_reinserts_at_the_target() {
+        let mut tasks = named(&["a", "b", "c", "d"]);
+        assert_eq!(move_task(&mut tasks, 3, 1), 1);
+        assert_eq!(descriptions(&tasks), ["a", "d", "b", "c"]);
+        assert_eq!(move_task(&mut tasks, 0, 2), 2);
+        assert_eq!(descriptions(&tasks), ["d", "b", "a", "c"]);
+    }
+
+    #[test]
+    fn move_task_clamps_a_target_past_the_end() {
+        let mut tasks = named(&["a", "b", "c"]);
+        assert_eq!(move_task(&mut tasks, 0, 3), 2);
+        assert_eq!(descriptions(&tasks), ["b", "c", "a"]);
+        assert_eq!(move_task(&mut tasks, 1, 99), 2);
+        assert_eq!(descriptions(&tasks), ["b", "a", "c"]);
+        assert_eq!(move_task(&mut tasks, 2, 2), 2);
+        assert_eq!(descriptions(&tasks), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn export_appending_twice_keeps_one_header() {
+        let path = std::env::temp_dir().join(format!("todo-export-{}.csv", process::id()));
+        let _ = fs::remove_file(&path);
+        let mut tasks = vec![new_task("a, with comma".into(), Some(Priority::High), None, vec!["x".into(), "y".into()])];
+        export_tasks(&tasks, &path, true).unwrap();
+        tasks[0].completed = true;
+        export_tasks(&tasks, &path, true).unwrap();
+        let data = fs::read_to_string(&path).unwrap();
+
+        assert_eq!(
+            data,
+            "id,status,priority,due,tags,completed_at,description\n\
+             0,pending,high,,x;y,,\"a, with comma\"\n\
+             0,done,high,,x;y,,\"a, with comma\"\n"
+        );
+
+        // Without --append the file starts over
+        export_tasks(&tasks, &path, false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn due_within_today_and_the_week() {
+        let today = date("2024-03-10");
+        let due = |day: &str| new_task("t".into(), None, Some(date(day)), Vec::new());
+        let undated = new_task("t".into(), None, None, Vec::new());
+
+        assert!(due_within(&due("2024-03-10"), today, 1));
+        assert!(!due_within(&due("2024-03-11"), today, 1));
+        assert!(!due_within(&due("2024-03-09"), today, 1));
+
+        assert!(due_within(&due("2024-03-10"), today, 7));
+        assert!(due_within(&due("2024-03-16"), today, 7));
+        assert!(!due_within(&due("2024-03-17"), today, 7));
+        // Overdue tasks aren't "due this week"
+        assert!(!due_within(&due("2024-03-09"), today, 7));
+
+        assert!(!due_within(&undated, today, 1));
+        assert!(!due_within(&undated, today, 7));
+    }
+
+    #[test]
+    fn recur_advances_by_its_interval() {
+        assert_eq!(Recur::Daily.advance(date("2024-02-28")), date("2024-02-29"));
+        assert_eq!(Recur::Weekly.advance(date("2024-12-30")), date("2025-01-06"));
+        assert_eq!(Recur::Monthly.advance(date("2024-01-15")), date("2024-02-15"));
+        // Clamped to the end of a shorter month
+        assert_eq!(Recur::Monthly.advance(date("2024-01-31")), date("2024-02-29"));
+    }
+
+    #[test]
+    fn completing_a_daily_task_moves_it_a_day_on() {
+        let mut task = new_task("standup".into(), None, Some(date("2024-03-10")), Vec::new());
+        task.recur = Some(Recur::Daily);
+        assert_eq!(complete(&mut task, date("2024-03-10")), Some(date("2024-03-11")));
+        assert_eq!(task.due, Some(date("2024-03-11")));
+        assert!(!task.completed);
+        assert_eq!(task.completed_at, None);
+    }
+
+    #[test]
+    fn completing_an_undated_recurring_task_starts_from_today() {
+        let mut task = new_task("review".into(), None, None, Vec::new());
+        task.recur = Some(Recur::Weekly);
+        assert_eq!(complete(&mut task, date("2024-03-10")), Some(date("2024-03-17")));
+    }
+
+    #[test]
+    fn completing_a_plain_task_marks_it_done() {
+        let mut task = new_task("once".into(), None, Some(date("2024-03-10")), Vec::new());
+        assert_eq!(complete(&mut task, date("2024-03-12")), None);
+        assert!(task.completed);
+        assert_eq!(task.completed_at, Some(date("2024-03-12")));
+        assert_eq!(task.due, Some(date("2024-03-10")));
+    }
+
+    #[test]
+    fn history_since_includes_and_excludes() {
+        let mut tasks = vec![new_task("write report".into(), None, None, Vec::new())];
+        complete(&mut tasks[0], date("2024-03-10"));
+
+        let since = |day| history(&tasks, &[], Some(date(day))).len();
+        assert_eq!(since("2024-03-09"), 1);
+        assert_eq!(since("2024-03-10"), 1);
+        assert_eq!(since("2024-03-11"), 0);
+    }
+
+    #[test]
+    fn history_lists_each_recurring_completion() {
+        let mut task = new_task("water plants".into(), None, Some(date("2024-03-10")), Vec::new());
+        task.recur = Some(Recur::Daily);
+        assert_eq!(complete(&mut task, date("2024-03-10")), Some(date("2024-03-11")));
+        assert_eq!(complete(&mut task, date("2024-03-11")), Some(date("2024-03-12")));
+        assert!(!task.completed);
+
+        let tasks = [task];
+        let dates: Vec<NaiveDate> = history(&tasks, &[], Some(date("2024-03-11"))).into_iter().map(|(d, _)| d).collect();
+        assert_eq!(dates, [date("2024-03-11")]);
+        assert_eq!(history(&tasks, &[], None).len(), 2);
+    }
+
+    #[test]
+    fn history_sorts_across_the_archive() {
+        let mut late = new_task("late".into(), None, None, Vec::new());
+        let mut early = new_task("early".into(), None, None, Vec::new());
+        complete(&mut late, date("2024-03-12"));
+        complete(&mut early, date("2024-03-01"));
+        let (tasks, archive) = ([late], [early]);
+        let names: Vec<&str> = history(&tasks, &archive, None).into_iter().map(|(_, t)| t.description.as_str()).collect();
+        assert_eq!(names, ["early", "late"]);
+    }
+
+    #[test]
+    fn undone_tasks_leave_the_history() {
+        let mut task = new_task("oops".into(), None, None, Vec::new());
+        complete(&mut task, date("2024-03-10"));
+        task.completed = false;
+        assert!(history(&[task], &[], None).is_empty());
+    }
+
+    fn days(dates: &[&str]) -> HashSet<NaiveDate> {
+        dates.iter().map(|d| date(d)).collect()
+    }
+
+    #[test]
+    fn streak_counts_back_from_today() {
+        let today = date("2024-03-10");
+        assert_eq!(streak(&days(&["2024-03-08", "2024-03-09", "2024-03-10"]), today), 3);
+        assert_eq!(streak(&days(&[]), today), 0);
+    }
+
+    #[test]
+    fn a_day_without_completions_breaks_the_streak() {
+        let done = days(&["2024-03-05", "2024-03-06", "2024-03-08", "2024-03-09", "2024-03-10"]);
+        assert_eq!(streak(&done, date("2024-03-10")), 3);
+        assert_eq!(streak(&done, date("2024-03-12")), 0);
+    }
+
+    #[test]
+    fn nothing_done_yet_today_keeps_yesterdays_streak() {
+        let done = days(&["2024-02-28", "2024-02-29"]);
+        assert_eq!(streak(&done, date("2024-03-01")), 2);
+    }
+}
\ No newline at end of file
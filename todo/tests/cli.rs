@@ -0,0 +1,174 @@
+//! End-to-end checks that run the built binary against a scratch task file.
+
+use chrono::{Days, Local};
+use std::{fs, path::PathBuf, process::Command};
+use tempfile::TempDir;
+
+/// A scratch directory holding the task file, which is also where the
+/// binary looks for its config file, so the user's own settings don't leak in.
+struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        Fixture { dir: tempfile::tempdir().unwrap() }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    fn file(&self, name: &str, contents: &str) -> PathBuf {
+        let path = self.path(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn tasks(&self) -> Vec<serde_json::Value> {
+        serde_json::from_str(&fs::read_to_string(self.path("tasks.json")).unwrap()).unwrap()
+    }
+
+    /// Runs a command that has to succeed, returning its stdout.
+    fn ok(&self, args: &[&str]) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_todo"))
+            .args(args)
+            .arg("--file")
+            .arg(self.path("tasks.json"))
+            .current_dir(self.dir.path())
+            .env("XDG_CONFIG_HOME", self.dir.path())
+            .env("NO_COLOR", "1")
+            .env_remove("TODO_FILE")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+}
+
+#[test]
+fn stats_counts_a_known_mix() {
+    let fx = Fixture::new();
+    let today = Local::now().date_naive();
+    let yesterday = (today - Days::new(1)).to_string();
+    let next_week = (today + Days::new(7)).to_string();
+    let in_two_days = (today + Days::new(2)).to_string();
+
+    fx.ok(&["add", "overdue", "-p", "high", "-d", &yesterday]);
+    fx.ok(&["add", "soon", "-p", "high", "-d", &in_two_days]);
+    fx.ok(&["add", "later", "-p", "low", "-d", &next_week]);
+    fx.ok(&["add", "whenever"]);
+    fx.ok(&["add", "done overdue", "-p", "medium", "-d", &yesterday]);
+    fx.ok(&["done", "4"]);
+
+    let out = fx.ok(&["stats"]);
+    let expected = [
+        "Total tasks: 5".to_string(),
+        "Completed: 1 (20.0%)".to_string(),
+        "Pending: 4".to_string(),
+        "  high: 2".to_string(),
+        "  medium: 1".to_string(),
+        "  low: 1".to_string(),
+        "  none: 1".to_string(),
+        // The completed task is past due too, but isn't counted
+        "Overdue: 1".to_string(),
+        format!("Next due: {}", in_two_days),
+        "Streak: 1 day".to_string(),
+    ];
+    for line in expected {
+        assert!(out.lines().any(|l| l == line), "missing {:?} in:\n{}", line, out);
+    }
+}
+
+#[test]
+fn stats_on_an_empty_list() {
+    let fx = Fixture::new();
+    let out = fx.ok(&["stats"]);
+    assert!(out.contains("Total tasks: 0\nCompleted: 0 (0.0%)\nPending: 0"), "{}", out);
+    assert!(out.contains("Next due: none"), "{}", out);
+}
+
+#[test]
+fn import_skips_blanks_and_comments() {
+    let fx = Fixture::new();
+    fx.ok(&["add", "already there"]);
+    fx.file("checklist.txt", "# Launch\n\n  buy domain  \n# later\nwrite docs\n   \n\tset up CI\n");
+
+    let out = fx.ok(&["import", "checklist.txt"]);
+    assert!(out.contains("Imported 3 tasks."), "{}", out);
+    let descriptions: Vec<String> = fx.tasks().iter().map(|t| t["description"].as_str().unwrap().to_string()).collect();
+    assert_eq!(descriptions, ["already there", "buy domain", "write docs", "set up CI"]);
+}
+
+#[test]
+fn import_of_only_comments_adds_nothing() {
+    let fx = Fixture::new();
+    fx.file("empty.txt", "# nothing\n\n");
+    assert!(fx.ok(&["import", "empty.txt"]).contains("Imported 0 tasks."));
+    assert!(!fx.path("tasks.json").exists());
+}
+
+#[test]
+fn week_view_respects_the_tag_filter() {
+    let fx = Fixture::new();
+    let today = Local::now().date_naive();
+    let soon = (today + Days::new(3)).to_string();
+    let later = (today + Days::new(30)).to_string();
+    fx.ok(&["add", "work soon", "-d", &soon, "-t", "work"]);
+    fx.ok(&["add", "home soon", "-d", &soon, "-t", "home"]);
+    fx.ok(&["add", "work later", "-d", &later, "-t", "work"]);
+    fx.ok(&["add", "work undated", "-t", "work"]);
+
+    let out = fx.ok(&["list", "--week", "--tag", "work", "--porcelain"]);
+    let listed: Vec<&str> = out.lines().map(|line| line.rsplit('\t').next().unwrap()).collect();
+    assert_eq!(listed, ["work soon"]);
+}
+
+#[test]
+fn porcelain_layout() {
+    let fx = Fixture::new();
+    fx.ok(&["add", "plain"]);
+    fx.ok(&["add", "full", "-p", "high", "-d", "2030-01-02", "-t", "x"]);
+    fx.ok(&["add", "tab\there"]);
+    fx.ok(&["done", "1"]);
+
+    assert_eq!(
+        fx.ok(&["list", "--porcelain"]),
+        "0\tpending\t-\t-\tplain\n\
+         1\tdone\thigh\t2030-01-02\tfull\n\
+         2\tpending\t-\t-\ttab here\n"
+    );
+    // Sorting reorders the lines but each keeps its stored id
+    let sorted = fx.ok(&["list", "--porcelain", "--sort", "priority"]);
+    assert!(sorted.starts_with("1\tdone\thigh"), "{}", sorted);
+}
+
+fn descriptions(fx: &Fixture) -> Vec<String> {
+    fx.tasks().iter().map(|t| t["description"].as_str().unwrap().to_string()).collect()
+}
+
+#[test]
+fn undo_brings_back_a_removed_task() {
+    let fx = Fixture::new();
+    fx.ok(&["add", "keep"]);
+    fx.ok(&["add", "oops"]);
+    fx.ok(&["remove", "1", "--force"]);
+    assert_eq!(descriptions(&fx), ["keep"]);
+
+    assert!(fx.ok(&["undo"]).contains("Restored 1 removed task(s)."));
+    assert_eq!(descriptions(&fx), ["keep", "oops"]);
+    // Only one level is kept
+    assert!(fx.ok(&["undo"]).contains("Nothing to undo."));
+    assert_eq!(descriptions(&fx), ["keep", "oops"]);
+}
+
+#[test]
+fn undo_reverts_other_changes_and_skips_read_only_commands() {
+    let fx = Fixture::new();
+    fx.ok(&["add", "task"]);
+    fx.ok(&["done", "0"]);
+    fx.ok(&["list"]);
+    fx.ok(&["stats"]);
+    assert!(fx.ok(&["undo"]).contains("Restored the previous task list."));
+    assert_eq!(fx.tasks()[0]["completed"], false);
+}
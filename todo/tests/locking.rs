@@ -0,0 +1,34 @@
+//! Runs several todo processes against one task file at the same time.
+
+use std::process::{Command, Stdio};
+
+#[test]
+fn concurrent_adds_all_survive() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("tasks.json");
+
+    // Enough processes that, without the lock, some would load the list
+    // before another saved and write over its task
+    let children: Vec<_> = (0..8)
+        .map(|i| {
+            Command::new(env!("CARGO_BIN_EXE_todo"))
+                .args(["add", &format!("task {}", i)])
+                .arg("--file")
+                .arg(&file)
+                .env("XDG_CONFIG_HOME", dir.path())
+                .env_remove("TODO_FILE")
+                .stdout(Stdio::null())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    for mut child in children {
+        assert!(child.wait().unwrap().success());
+    }
+
+    let tasks: Vec<serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    let mut descriptions: Vec<&str> = tasks.iter().map(|task| task["description"].as_str().unwrap()).collect();
+    descriptions.sort();
+    let expected: Vec<String> = (0..8).map(|i| format!("task {}", i)).collect();
+    assert_eq!(descriptions, expected);
+}